@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+/// Limits of a call to `MCTS::search`, the search stops as soon as any of
+/// them is reached. A budget without any limit stops right away.
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    pub iterations: Option<u32>,
+    pub time: Option<Duration>,
+    /// Total number of nodes in the tree.
+    pub nodes: Option<usize>,
+    /// Approximate memory used by the tree, in bytes.
+    pub memory: Option<usize>,
+}
+
+impl Budget {
+    pub fn new() -> Budget {
+        Budget::default()
+    }
+
+    pub fn iterations(mut self, iterations: u32) -> Budget {
+        self.iterations = Some(iterations);
+        self
+    }
+
+    pub fn time(mut self, time: Duration) -> Budget {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn nodes(mut self, nodes: usize) -> Budget {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    pub fn memory(mut self, bytes: usize) -> Budget {
+        self.memory = Some(bytes);
+        self
+    }
+
+    /// Returns the first limit reached after `iterations` iterations
+    /// started at `start`, with a tree of `nodes` nodes using `memory` bytes.
+    pub(crate) fn reached(
+        &self,
+        iterations: u32,
+        start: Instant,
+        nodes: usize,
+        memory: usize,
+    ) -> Option<StopReason> {
+        if self.iterations.is_some_and(|max| iterations >= max) {
+            Some(StopReason::Iterations)
+        } else if self.time.is_some_and(|max| start.elapsed() >= max) {
+            Some(StopReason::Time)
+        } else if self.nodes.is_some_and(|max| nodes >= max) {
+            Some(StopReason::Nodes)
+        } else if self.memory.is_some_and(|max| memory >= max) {
+            Some(StopReason::Memory)
+        } else if self.iterations.is_none()
+            && self.time.is_none()
+            && self.nodes.is_none()
+            && self.memory.is_none()
+        {
+            Some(StopReason::Unbounded)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why `MCTS::search` stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Iterations,
+    Time,
+    Nodes,
+    Memory,
+    /// The budget had no limit.
+    Unbounded,
+    /// The value of the root is proven, searching further is useless.
+    Solved,
+}
@@ -0,0 +1,11 @@
+/// The action is not one of the valid actions of the game.
+#[derive(Debug, PartialEq)]
+pub struct IllegalAction<A>(pub A);
+
+impl<A: std::fmt::Debug> std::fmt::Display for IllegalAction<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?} is not a valid action", self.0)
+    }
+}
+
+impl<A: std::fmt::Debug> std::error::Error for IllegalAction<A> {}
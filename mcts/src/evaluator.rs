@@ -0,0 +1,79 @@
+use crate::tree::{NodeId, Tree};
+use crate::{GameTest, UniformRandom};
+use rand::RngCore;
+
+/// What an `Evaluator` tells about a state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Evaluation {
+    /// Prior probability of each of the actions given to the evaluator, in
+    /// the same order.
+    pub priors: Vec<f64>,
+    /// Expected reward of each player, indexed by player.
+    pub value: Vec<f64>,
+}
+
+/// Evaluates the leaves of the search in place of the playouts, such as a
+/// trained policy and value network, see `MCTS::with_evaluator`.
+pub trait Evaluator<T: GameTest> {
+    /// Evaluates `g`, where the player to move can play `actions`. They are
+    /// empty when the next move is a random event.
+    fn evaluate(&self, g: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> Evaluation;
+
+    /// Evaluates every leaf of `leaves` in a single call, one evaluation
+    /// per leaf in the same order. Evaluates them one by one by default.
+    fn evaluate_batch(&self, leaves: &[Leaf<T>], rng: &mut dyn RngCore) -> Vec<Evaluation> {
+        leaves
+            .iter()
+            .map(|l| self.evaluate(&l.state, &l.actions, rng))
+            .collect()
+    }
+}
+
+/// A leaf selected by `MCTS::select_leaves`, waiting for its evaluation.
+pub struct Leaf<T: GameTest> {
+    /// State of the game at the leaf.
+    pub state: T,
+    /// Actions to give a prior, empty when the next move is a random event.
+    pub actions: Vec<T::Action>,
+    /// Path from the root, which holds a virtual loss until the back up.
+    pub(crate) path: Vec<NodeId>,
+}
+
+/// Reference evaluator, computed on the CPU without any model: priors come
+/// from `GameTest::action_prior`, uniform when the game gives none, and the
+/// value is the mean reward of a number of random playouts.
+pub struct RolloutEvaluator {
+    pub playouts: u32,
+}
+
+impl Default for RolloutEvaluator {
+    fn default() -> RolloutEvaluator {
+        RolloutEvaluator { playouts: 1 }
+    }
+}
+
+impl<T: GameTest + Clone> Evaluator<T> for RolloutEvaluator {
+    fn evaluate(&self, g: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> Evaluation {
+        let mut priors: Vec<f64> = actions
+            .iter()
+            .map(|a| g.action_prior(a).unwrap_or(1.0))
+            .collect();
+        let total: f64 = priors.iter().sum();
+        for p in priors.iter_mut() {
+            *p = if total > 0.0 {
+                *p / total
+            } else {
+                1.0 / actions.len() as f64
+            };
+        }
+
+        let mut value = vec![0.0; g.players()];
+        for _ in 0..self.playouts {
+            let rewards = Tree::simulate(&mut g.clone(), &UniformRandom, rng, None);
+            for (v, r) in value.iter_mut().zip(rewards) {
+                *v += r / self.playouts as f64;
+            }
+        }
+        Evaluation { priors, value }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::Node;
+
+/// How `MCTS::play_best_move` picks the move to play once the search is
+/// done.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FinalMove {
+    /// The most visited child, the default.
+    #[default]
+    Robust,
+    /// The child with the highest mean reward.
+    Max,
+    /// The child with the highest lower confidence bound
+    /// `mean - a / sqrt(plays)`, with `a` the given constant.
+    Secure(f64),
+    /// The child that is both the most visited and the highest valued. The
+    /// search goes on for up to the given number of extra iterations until
+    /// there is one, then falls back to `Robust`.
+    MaxRobust(u32),
+}
+
+fn mean<A>(t: &Node<A>) -> f64 {
+    t.reward() / t.stats.plays as f64
+}
+
+/// Index of the child with the highest `score`, unvisited children are
+/// never picked. `None` when no child was visited.
+fn best_by<A, F: Fn(&Node<A>) -> f64>(children: &[&Node<A>], score: F) -> Option<usize> {
+    let mut best = f64::NEG_INFINITY;
+    children.iter().enumerate().fold(None, |acc, (index, t)| {
+        if t.stats.plays == 0 {
+            return acc;
+        }
+        let new_score = score(t);
+        if new_score > best {
+            best = new_score;
+            Some(index)
+        } else {
+            acc
+        }
+    })
+}
+
+impl FinalMove {
+    pub(crate) fn robust_index<A>(children: &[&Node<A>]) -> Option<usize> {
+        best_by(children, |t| t.stats.plays as f64)
+    }
+
+    pub(crate) fn max_index<A>(children: &[&Node<A>]) -> Option<usize> {
+        best_by(children, mean)
+    }
+
+    /// Index of the child to play, `None` when no child was visited or
+    /// `MaxRobust` needs more search.
+    pub(crate) fn index<A>(self, children: &[&Node<A>]) -> Option<usize> {
+        match self {
+            FinalMove::Robust => FinalMove::robust_index(children),
+            FinalMove::Max => FinalMove::max_index(children),
+            FinalMove::Secure(a) => {
+                best_by(children, |t| mean(t) - a / (t.stats.plays as f64).sqrt())
+            }
+            FinalMove::MaxRobust(_) => {
+                let robust = FinalMove::robust_index(children)?;
+                if Some(robust) == FinalMove::max_index(children) {
+                    Some(robust)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
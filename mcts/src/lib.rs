@@ -1,218 +1,1939 @@
 extern crate rand;
-use rand::distributions::{uniform::Uniform, Distribution};
+use rand::distributions::{Dirichlet, Distribution};
+use rand::rngs::StdRng;
+use rand::{FromEntropy, RngCore, SeedableRng};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::Instant;
+
+mod budget;
+mod error;
+mod evaluator;
+mod final_move;
+mod parallel;
+mod priors;
+mod rave;
+mod rollout;
+mod selection;
+mod self_play;
+mod simultaneous;
+mod tree;
+mod tree_parallel;
+mod widening;
+pub use budget::{Budget, StopReason};
+pub use error::IllegalAction;
+pub use evaluator::{Evaluation, Evaluator, Leaf, RolloutEvaluator};
+pub use final_move::FinalMove;
+pub use parallel::{RootChild, RootParallel};
+pub use priors::Priors;
+pub use rave::Rave;
+pub use rollout::{EpsilonGreedy, RolloutPolicy, UniformRandom, WinningMove};
+pub use selection::{ChildStats, Puct, SelectionPolicy, Thompson, Ucb1, Ucb1Tuned, UcbV};
+pub use self_play::{read_records, Noise, Record, RecordWriter, SelfPlay, FORMAT_VERSION};
+pub use simultaneous::{Bandit, SimultaneousGame, SimultaneousMCTS};
+use tree::{sample, Node, NodeId, Stats, Tree};
+pub use tree_parallel::TreeParallel;
+pub use widening::Widening;
 
 pub enum PlayRes {
     Nothing,
     Win,
     Loose,
+    Draw,
+    /// Game over with the given reward for each player, indexed by player.
+    Rewards(Vec<f64>),
+}
+
+/// Reward of each player at the end of a game.
+type Rewards = Vec<f64>;
+
+/// Splits `total` evenly between all players but `except`.
+fn share(players: usize, except: Option<usize>, total: f64) -> Rewards {
+    let n = match except {
+        Some(_) => players - 1,
+        None => players,
+    };
+    (0..players)
+        .map(|p| {
+            if Some(p) == except {
+                0.0
+            } else {
+                total / n as f64
+            }
+        })
+        .collect()
 }
 
+/// Whether `play` is a legal move of `g`, or one of its chance outcomes
+/// when a random event is pending.
+fn is_legal<T: GameTest>(g: &T, play: &T::Action) -> bool {
+    let outcomes = g.chance_outcomes();
+    if outcomes.is_empty() {
+        g.valid_actions().contains(play)
+    } else {
+        outcomes.iter().any(|(a, _)| a == play)
+    }
+}
+
+/// A game between `players()` players numbered from `0`. `play` reports the
+/// result from the point of view of the player who made the move: a `Win`
+/// is worth `1.0` to them, a `Loose` shares that point between the other
+/// players and a `Draw` shares it between everyone.
+///
+/// Each node keeps the reward of every player and selection maximises the
+/// one of the player to move (max^n), which is negamax for two players.
 pub trait GameTest {
-    fn play(&mut self, play: usize) -> PlayRes;
-    fn valid_actions(&self) -> Vec<usize>;
+    type Action: Clone + Eq + Hash + Debug;
+
+    fn play(&mut self, play: Self::Action) -> PlayRes;
+    fn valid_actions(&self) -> Vec<Self::Action>;
+    fn current_player(&self) -> usize;
+
+    fn players(&self) -> usize {
+        2
+    }
+
+    /// Outcomes of the random event, such as a dice roll, to resolve before
+    /// the next move, with their probabilities. The outcome that happens is
+    /// then given to `play`, which reports the result from the point of
+    /// view of `current_player` if it ends the game. `valid_actions` is not
+    /// used while there are outcomes. No random event by default.
+    fn chance_outcomes(&self) -> Vec<(Self::Action, f64)> {
+        Vec::new()
+    }
+
+    /// Sorts `actions` from the most to the least promising, the order in
+    /// which progressive widening unlocks them. Keeps the order of
+    /// `valid_actions` by default.
+    fn order_actions(&self, _actions: &mut [Self::Action]) {}
+
+    /// Heuristic value of playing `action` for the player to move, from
+    /// `0.0` to `1.0`, such as `1.0` for a move that wins. Only used with
+    /// `MCTS::with_priors`.
+    fn action_prior(&self, _action: &Self::Action) -> Option<f64> {
+        None
+    }
+
+    /// A full state that `observer` cannot tell apart from this one, drawn
+    /// at random, such as with the cards they do not see dealt again. Used by
+    /// `MCTS::with_information_sets`, `None` for games where everything is
+    /// visible.
+    fn determinize(&self, _observer: usize, _rng: &mut dyn RngCore) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Features of the state for a learned model, such as one plane per
+    /// kind of piece, recorded by `MCTS::self_play`. `None` by default.
+    fn encode(&self) -> Option<Vec<f32>> {
+        None
+    }
+
+    /// Index of `action` in the policy of a learned model, recorded by
+    /// `MCTS::self_play`. Its position in `valid_actions` by default, `None`
+    /// leaves the move out of the record.
+    fn action_index(&self, action: &Self::Action) -> Option<usize> {
+        self.valid_actions().iter().position(|a| a == action)
+    }
+
+    /// Hash identifying the state of the game, used to share the nodes of
+    /// transposed positions, see `MCTS::with_transpositions`. States that
+    /// can repeat within a game must hash differently.
+    fn state_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Highest reward a player can get, if there is one. With
+    /// `MCTS::with_solver`, a move proven to give it to the player to move
+    /// solves their node before the other moves are.
+    fn max_reward(&self) -> Option<f64> {
+        None
+    }
+}
+
+pub struct MCTS<T: GameTest> {
+    tree: Tree<T::Action>,
+    selection: Box<dyn SelectionPolicy + Send>,
+    rollout: Box<dyn RolloutPolicy<T> + Send>,
+    final_move: FinalMove,
+    /// Evaluates the leaves in place of the playouts when set.
+    evaluator: Option<Box<dyn Evaluator<T> + Send>>,
+    /// Leaves selected and waiting for their evaluation.
+    pending: Vec<Leaf<T>>,
+    /// Leaves evaluated at once by `search`.
+    batch: u32,
+    /// Source of every random choice of the search.
+    rng: Box<dyn RngCore + Send>,
+}
+
+impl<T: GameTest> std::fmt::Debug for MCTS<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let root = &self.tree[self.tree.root];
+        let res = self.tree.fmt(self.tree.root, 1);
+        write!(f, " @:{:2}/{:2}--{}", root.reward(), root.stats.plays, res)
+    }
 }
 
-#[derive(Clone)]
-struct Tree {
-    children: Vec<Tree>,
-    plays: u32,
-    wins: u32,
-    action: usize,
+impl<T: GameTest + Clone> Default for MCTS<T> {
+    fn default() -> MCTS<T> {
+        MCTS::new()
+    }
 }
 
-impl Tree {
-    fn best_child_index(&self) -> usize {
-        let mut score = 0.0;
-        self.children.iter().enumerate().fold(0, |acc, (index, t)| {
-            if t.plays == 0 {
-                score = 1.0;
-                index
-            } else if t.wins as f32 / t.plays as f32 > score {
-                score = t.wins as f32 / t.plays as f32;
-                index
+impl<T: GameTest + Clone> MCTS<T> {
+    pub fn new() -> MCTS<T> {
+        MCTS {
+            tree: Tree::new(),
+            selection: Box::new(Ucb1::default()),
+            rollout: Box::new(UniformRandom),
+            final_move: FinalMove::default(),
+            evaluator: None,
+            pending: Vec::new(),
+            batch: 1,
+            rng: Box::new(StdRng::from_entropy()),
+        }
+    }
+
+    /// Makes the search reproducible: the same seed, game and budget always
+    /// give the same tree.
+    pub fn with_seed(self, seed: u64) -> MCTS<T> {
+        self.with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Uses `rng` for every random choice, seeded from the system by
+    /// default.
+    pub fn with_rng<R: RngCore + Send + 'static>(mut self, rng: R) -> MCTS<T> {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Uses `selection` to choose which child to explore, UCB1 by default.
+    pub fn with_selection<S: SelectionPolicy + Send + 'static>(mut self, selection: S) -> MCTS<T> {
+        self.selection = Box::new(selection);
+        self
+    }
+
+    /// Uses `rollout` to play the simulations, uniformly random by default.
+    pub fn with_rollout<R: RolloutPolicy<T> + Send + 'static>(mut self, rollout: R) -> MCTS<T> {
+        self.rollout = Box::new(rollout);
+        self
+    }
+
+    /// Uses `final_move` to pick the move to play, the most visited one by
+    /// default.
+    pub fn with_final_move(mut self, final_move: FinalMove) -> MCTS<T> {
+        self.final_move = final_move;
+        self
+    }
+
+    /// Records all-moves-as-first statistics during the playouts and blends
+    /// them into the values seen by the selection policy following
+    /// `schedule`.
+    pub fn with_rave(mut self, schedule: Rave) -> MCTS<T> {
+        self.tree.rave = Some(schedule);
+        self
+    }
+
+    /// Unlocks the children of a node gradually as it gets visited, instead
+    /// of creating and simulating all of them on its first visit. The game
+    /// gives the order with `GameTest::order_actions`.
+    pub fn with_widening(mut self, widening: Widening) -> MCTS<T> {
+        self.tree.widening = Some(widening);
+        self
+    }
+
+    /// Guides the search with the priors of `GameTest::action_prior`, which
+    /// early on weigh more than the few playouts of a child.
+    pub fn with_priors(mut self, priors: Priors) -> MCTS<T> {
+        self.tree.priors = Some(priors);
+        self
+    }
+
+    /// AlphaZero-style search: each new leaf is evaluated by `evaluator`
+    /// instead of simulating its children, its value is backed up and its
+    /// priors weigh the children in PUCT, which becomes the selection
+    /// policy. A later `with_selection` replaces it.
+    pub fn with_evaluator<E: Evaluator<T> + Send + 'static>(mut self, evaluator: E) -> MCTS<T> {
+        self.evaluator = Some(Box::new(evaluator));
+        self.selection = Box::new(Puct::default());
+        self
+    }
+
+    /// Evaluates up to `size` leaves at once in `search` when there is an
+    /// evaluator, one by default. See `train_batch`.
+    pub fn with_batch(mut self, size: u32) -> MCTS<T> {
+        self.batch = size.max(1);
+        self
+    }
+
+    /// Searches games of hidden information with information-set MCTS: each
+    /// iteration plays one determinization drawn by `GameTest::determinize`
+    /// for the player to move, and nodes gather the statistics of an action
+    /// sequence over all of them. Solving and transpositions are not used.
+    pub fn with_information_sets(mut self) -> MCTS<T> {
+        self.tree.information_sets = true;
+        self
+    }
+
+    /// Shares the nodes of positions reached through different move orders,
+    /// for games that provide a `state_hash`. Visits are still backed up
+    /// along the path actually played.
+    pub fn with_transpositions(mut self) -> MCTS<T> {
+        self.tree.transpositions = Some(HashMap::new());
+        self
+    }
+
+    /// Proves the value of nodes whose outcome is known under perfect play
+    /// and stops searching their subtrees, see `solved`. The search then
+    /// stops as soon as the root is solved.
+    pub fn with_solver(mut self) -> MCTS<T> {
+        self.tree.solver = true;
+        self
+    }
+
+    pub fn train(&mut self, g: &mut T) {
+        if self.evaluator.is_some() {
+            self.train_batch(g, 1);
+            return;
+        }
+        let (new_g, path, terminal) = self.select_leaf(g);
+        let leaf = *path.last().unwrap();
+
+        let players = new_g.players();
+        let mut acc = Stats::new(players);
+        // Moves after the leaf and rewards of each playout, for RAVE.
+        let rave = self.tree.rave.is_some();
+        let mut playouts = Vec::new();
+
+        let information_sets = self.tree.information_sets;
+        let node = &self.tree[leaf];
+        // A transposed leaf unlocks the children of the node holding them.
+        let owner = match information_sets {
+            true => leaf,
+            false => self.tree.owner(leaf, &new_g, &path),
+        };
+        let children = match (&terminal, &node.terminal, &node.proven) {
+            (None, None, None) => self.tree.expand(owner, &new_g),
+            _ => Vec::new(),
+        };
+        if !information_sets {
+            self.tree.transpose(leaf, &new_g, &path);
+        }
+
+        if children.is_empty() {
+            let node = &mut self.tree[leaf];
+            let rewards = match (terminal, &node.proven) {
+                (Some(rewards), _) => rewards,
+                (None, Some(rewards)) => rewards.clone(),
+                // The end of one determinization only.
+                (None, None) if information_sets => share(players, None, 1.0),
+                (None, None) => node
+                    .terminal
+                    .get_or_insert_with(|| share(players, None, 1.0))
+                    .clone(),
+            };
+            acc.push(&rewards);
+            if rave {
+                playouts.push((Vec::new(), rewards));
+            }
+        } else {
+            self.tree.insert(leaf, &new_g);
+
+            for l in children {
+                let mut sim_g = new_g.clone();
+                let action = self.tree[l].action.clone().unwrap();
+                let mut played = vec![(self.tree[l].player, action.clone())];
+                let terminal = Tree::play_out(&mut sim_g, action);
+                let record = if rave { Some(&mut played) } else { None };
+                let rewards = match &terminal {
+                    Some(rewards) => rewards.clone(),
+                    None => Tree::simulate(&mut sim_g, &*self.rollout, &mut *self.rng, record),
+                };
+
+                if !information_sets {
+                    if self.tree.solver {
+                        self.tree[l].proven = terminal.clone();
+                    }
+                    self.tree[l].terminal = terminal;
+                }
+                self.tree[l].available += 1;
+                self.tree[l].stats.push(&rewards);
+                acc.push(&rewards);
+                if rave {
+                    playouts.push((played, rewards));
+                }
+            }
+        }
+
+        self.tree.backprop(&path, &acc);
+        self.tree.prove(&path, new_g.max_reward());
+        if rave {
+            for (played, rewards) in playouts {
+                self.tree.backprop_amaf(&path, &played, &rewards);
+            }
+        }
+    }
+
+    /// Selection phase of an iteration from `g`: returns the state reached,
+    /// the path to the leaf and the final rewards if the game ended on the
+    /// way.
+    fn select_leaf(&mut self, g: &T) -> (T, Vec<NodeId>, Option<Rewards>) {
+        let mut new_g: T = g.clone();
+        let (path, terminal) = if self.tree.information_sets {
+            if let Some(determinized) = g.determinize(g.current_player(), &mut *self.rng) {
+                new_g = determinized;
+            }
+            self.tree
+                .select_determinized(&mut new_g, &*self.selection, &mut *self.rng)
+        } else {
+            let path = self
+                .tree
+                .select(&mut new_g, &*self.selection, &mut *self.rng);
+            (path, None)
+        };
+        (new_g, path, terminal)
+    }
+
+    /// Backs up the rewards of one iteration along `path`, which ends in
+    /// the state `g`.
+    fn finish(&mut self, path: &[NodeId], rewards: &[f64], g: &T) {
+        let mut acc = Stats::new(rewards.len());
+        acc.push(rewards);
+        self.tree.backprop(path, &acc);
+        self.tree.prove(path, g.max_reward());
+    }
+
+    /// First stage of a batched iteration: selects up to `n` leaves from
+    /// `g` and returns how many iterations ran. Each path holds a virtual
+    /// loss so that the next ones spread out, and the leaves wait in
+    /// `pending` for `back_up`, before which the root must not move. Leaves
+    /// whose rewards are already known are backed up at once. Stops early
+    /// when a pending leaf is selected again.
+    pub fn select_leaves(&mut self, g: &T, n: u32) -> u32 {
+        let mut iterations = 0;
+        while iterations < n {
+            let (state, path, terminal) = self.select_leaf(g);
+            let leaf = *path.last().unwrap();
+            if self.pending.iter().any(|l| l.path.last() == Some(&leaf)) {
+                break;
+            }
+            iterations += 1;
+
+            let node = &self.tree[leaf];
+            let known = terminal
+                .or_else(|| node.proven.clone())
+                .or_else(|| node.terminal.clone());
+            if let Some(rewards) = known {
+                self.finish(&path, &rewards, &state);
+                continue;
+            }
+            for &id in &path {
+                self.tree[id].virtual_loss += 1;
+            }
+            let actions = match state.chance_outcomes().is_empty() {
+                true => state.valid_actions(),
+                false => Vec::new(),
+            };
+            self.pending.push(Leaf {
+                state,
+                actions,
+                path,
+            });
+        }
+        iterations
+    }
+
+    /// Leaves selected by `select_leaves` and not backed up yet.
+    pub fn pending(&self) -> &[Leaf<T>] {
+        &self.pending
+    }
+
+    /// Last stage of a batched iteration: expands every pending leaf with
+    /// its evaluation, given in the same order, and backs up its value. The
+    /// new children are not visited, only checked for the end of the game.
+    pub fn back_up(&mut self, evaluations: Vec<Evaluation>) {
+        assert_eq!(evaluations.len(), self.pending.len());
+        let (information_sets, solver) = (self.tree.information_sets, self.tree.solver);
+        let pending = std::mem::take(&mut self.pending);
+        for (leaf, evaluation) in pending.into_iter().zip(evaluations) {
+            for &id in &leaf.path {
+                self.tree[id].virtual_loss -= 1;
+            }
+            let id = *leaf.path.last().unwrap();
+            let g = &leaf.state;
+            // A leaf transposed to a position expanded earlier in the batch
+            // only links to its children.
+            let owner = match information_sets {
+                true => id,
+                false => self.tree.owner(id, g, &leaf.path),
+            };
+            let children = match owner == id {
+                true => self.tree.expand(id, g),
+                false => Vec::new(),
+            };
+            if owner != id {
+                self.tree.transpose(id, g, &leaf.path);
+            } else if children.is_empty() {
+                let draw = share(g.players(), None, 1.0);
+                if !information_sets {
+                    self.tree[id].terminal = Some(draw.clone());
+                }
+                self.finish(&leaf.path, &draw, g);
+                continue;
+            }
+
+            self.tree.insert(id, g);
+            for &l in &children {
+                let action = self.tree[l].action.clone().unwrap();
+                let index = leaf.actions.iter().position(|a| *a == action);
+                let terminal = Tree::play_out(&mut g.clone(), action);
+                let child = &mut self.tree[l];
+                if !information_sets {
+                    if solver {
+                        child.proven = terminal.clone();
+                    }
+                    child.terminal = terminal;
+                }
+                child.available += 1;
+                child.prior = index.and_then(|i| evaluation.priors.get(i).cloned());
+            }
+            self.finish(&leaf.path, &evaluation.value, g);
+        }
+    }
+
+    /// Runs up to `n` iterations at once: selects the leaves, evaluates
+    /// them in a single call to `Evaluator::evaluate_batch` and backs them
+    /// all up. Returns how many iterations ran. Panics without an
+    /// evaluator.
+    pub fn train_batch(&mut self, g: &T, n: u32) -> u32 {
+        let iterations = self.select_leaves(g, n);
+        let evaluator = self.evaluator.as_ref().expect("batches need an evaluator");
+        let evaluations = evaluator.evaluate_batch(&self.pending, &mut *self.rng);
+        self.back_up(evaluations);
+        iterations
+    }
+
+    /// Trains until one of the limits of `budget` is reached or the root is
+    /// solved, and returns why it stopped.
+    pub fn search(&mut self, g: &mut T, budget: &Budget) -> StopReason {
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            if self.solved().is_some() {
+                return StopReason::Solved;
+            }
+            if let Some(reason) = budget.reached(iterations, start, self.nodes(), self.memory()) {
+                return reason;
+            }
+            if self.evaluator.is_some() {
+                let left = budget.iterations.map_or(u32::MAX, |max| max - iterations);
+                iterations += self.train_batch(g, self.batch.min(left));
             } else {
-                acc
+                self.train(g);
+                iterations += 1;
             }
-        })
+        }
     }
 
-    fn explore_index(&self, total_step: u32) -> usize {
-        let sqrt2 = (2.0 as f32).sqrt();
-        let tot_step_ln = (total_step as f32).ln();
+    /// Exact rewards of each player under perfect play from the root, once
+    /// the search has proven them.
+    pub fn solved(&self) -> Option<&[f64]> {
+        self.tree[self.tree.root].proven.as_deref()
+    }
+
+    /// Number of nodes in the tree.
+    pub fn nodes(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Approximate memory used by the tree, in bytes.
+    pub fn memory(&self) -> usize {
+        self.tree.memory()
+    }
+
+    /// Plays `play`, an opponent move or the outcome of a random event, on
+    /// `g` and moves the root of the tree to it. The subtree already
+    /// explored is kept when there is one, the search restarts from a
+    /// fresh root otherwise.
+    pub fn apply_ext(
+        &mut self,
+        g: &mut T,
+        play: T::Action,
+    ) -> Result<(), IllegalAction<T::Action>> {
+        if !is_legal(g, &play) {
+            return Err(IllegalAction(play));
+        }
+
+        self.advance(g, play);
+        self.train(g);
+        Ok(())
+    }
+
+    /// Plays `play` on `g` and moves the root of the tree to it, keeping the
+    /// subtree already explored when there is one. Returns the final rewards
+    /// if the game is over.
+    fn advance(&mut self, g: &mut T, play: T::Action) -> Option<Rewards> {
+        let root = self.tree.root;
+        let child = self.tree[root]
+            .children
+            .iter()
+            .find(|&&c| self.tree[c].action.as_ref() == Some(&play))
+            .cloned();
+        match child {
+            Some(id) => {
+                self.tree.reroot(id);
+                Tree::play_out(g, play)
+            }
+            None => {
+                let mut node = Node::new(Some(play.clone()), g.current_player(), Stats::default());
+                node.terminal = Tree::play_out(g, play);
+                if self.tree.solver {
+                    node.proven = node.terminal.clone();
+                }
+                let terminal = node.terminal.clone();
+                let id = self.tree.add(node);
+                self.tree.reroot(id);
+                terminal
+            }
+        }
+    }
+
+    /// Mixes `noise` into the priors of the root children.
+    fn add_noise(&mut self, noise: Noise) {
+        let root = self.tree.root;
+        let children = self.tree[root].children.clone();
+        if children.len() < 2 || self.tree.is_chance(root) {
+            return;
+        }
+        let etas = Dirichlet::new_with_param(noise.alpha, children.len()).sample(&mut *self.rng);
+        let uniform = 1.0 / children.len() as f64;
+        let total: f64 = children.iter().filter_map(|&c| self.tree[c].prior).sum();
+        for (&c, eta) in children.iter().zip(etas) {
+            let prior = match self.tree[c].prior {
+                Some(prior) if total > 0.0 => prior / total,
+                _ => uniform,
+            };
+            self.tree[c].prior = Some((1.0 - noise.fraction) * prior + noise.fraction * eta);
+        }
+    }
 
-        let mut score = 0.0;
-        self.children.iter().enumerate().fold(0, |acc, (index, t)| {
-            if t.plays == 0 {
-                score = 100000.0;
-                index
+    /// Plays `g` to the end against itself, searching each move within
+    /// `settings`, and returns the record of every position where a move
+    /// was chosen, ready for a `RecordWriter`. Random events are drawn
+    /// instead, and not recorded.
+    pub fn self_play(&mut self, g: &mut T, settings: &SelfPlay) -> Vec<Record> {
+        let mut records = Vec::new();
+        let mut moves = 0;
+        let outcome = loop {
+            let mut outcomes = g.chance_outcomes();
+            let play = if !outcomes.is_empty() {
+                let index = sample(outcomes.iter().map(|o| o.1), &mut *self.rng);
+                outcomes.swap_remove(index).0
+            } else if g.valid_actions().is_empty() {
+                break share(g.players(), None, 1.0);
             } else {
-                let new_score =
-                    t.wins as f32 / t.plays as f32 + sqrt2 * (tot_step_ln / t.plays as f32).sqrt();
-                if new_score > score {
-                    score = new_score;
-                    index
+                let root = self.tree.root;
+                if self.tree[root].children.is_empty() {
+                    self.train(g);
+                }
+                if let Some(noise) = settings.noise {
+                    self.add_noise(noise);
+                }
+                self.search(g, &settings.budget);
+
+                let children = self.tree.children(root);
+                let total = children.iter().map(|c| c.stats.plays).sum::<u32>().max(1);
+                records.push(Record {
+                    player: g.current_player(),
+                    state: g.encode().unwrap_or_default(),
+                    visits: children
+                        .iter()
+                        .filter_map(|c| {
+                            let index = g.action_index(c.action.as_ref().unwrap())?;
+                            Some((index, c.stats.plays as f32 / total as f32))
+                        })
+                        .collect(),
+                    outcome: Vec::new(),
+                });
+
+                let max = children.iter().map(|c| c.stats.plays).max().unwrap_or(0);
+                let index = if moves < settings.temperature_moves
+                    && settings.temperature > 0.0
+                    && max > 0
+                {
+                    let weights = children.iter().map(|c| {
+                        (c.stats.plays as f64 / max as f64).powf(1.0 / settings.temperature)
+                    });
+                    sample(weights, &mut *self.rng)
                 } else {
-                    acc
+                    self.tree
+                        .proven_index(root)
+                        .or_else(|| FinalMove::robust_index(&children))
+                        .expect("the root has a move")
+                };
+                moves += 1;
+                children[index].action.clone().unwrap()
+            };
+            if let Some(rewards) = self.advance(g, play) {
+                break rewards;
+            }
+        };
+
+        let outcome: Vec<f32> = outcome.iter().map(|&r| r as f32).collect();
+        for record in records.iter_mut() {
+            record.outcome = outcome.clone();
+        }
+        records
+    }
+
+    fn update(&mut self, g: &mut T, id: NodeId) {
+        self.tree.reroot(id);
+
+        g.play(self.tree[id].action.clone().unwrap());
+
+        self.train(g);
+    }
+
+    /// Index of the child picked by `final_move`, among the children not
+    /// proven lost.
+    fn final_index(&self, final_move: FinalMove) -> Option<usize> {
+        let (indices, children) = self.tree.candidates(self.tree.root);
+        final_move.index(&children).map(|i| indices[i])
+    }
+
+    /// Plays the best move found so far on `g` and returns it, `None` when
+    /// the game is over or while a random event is pending, whose outcome
+    /// is given to `apply_ext`. With the solver, a move proven best is
+    /// played as soon as it is known.
+    pub fn play_best_move(&mut self, g: &mut T) -> Option<T::Action> {
+        if !g.chance_outcomes().is_empty() {
+            return None;
+        }
+        self.train(g);
+        let root = self.tree.root;
+        if self.tree[root].children.is_empty() {
+            return None;
+        }
+
+        let mut best_index = self
+            .tree
+            .proven_index(root)
+            .or_else(|| self.final_index(self.final_move));
+        if let FinalMove::MaxRobust(extra) = self.final_move {
+            for _ in 0..extra {
+                if best_index.is_some() {
+                    break;
                 }
+                self.train(g);
+                best_index = self.final_index(self.final_move);
             }
-        })
+        }
+
+        let best_index = match best_index {
+            Some(index) => index,
+            None => self.final_index(FinalMove::Robust)?,
+        };
+        let id = self.tree[root].children[best_index];
+        self.update(g, id);
+        self.tree[id].action.clone()
     }
+}
 
-    fn get_at(&mut self, path: &Vec<usize>, from: usize, at: usize) -> &mut Tree {
-        if from == at {
-            self
-        } else {
-            self.children[path[from]].get_at(path, from + 1, at)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    #[derive(Clone)]
+    struct Morpion {
+        board: [Option<usize>; 9],
+        next: usize,
+    }
+
+    impl Morpion {
+        fn from_moves(moves: &[usize]) -> Morpion {
+            let mut g = Morpion {
+                board: [None; 9],
+                next: 0,
+            };
+            for &m in moves {
+                g.play(m);
+            }
+            g
         }
     }
 
-    fn select<T: GameTest>(&mut self, g: &mut T, total_step: u32) -> Vec<usize> {
-        if self.children.len() == 0 {
-            vec![]
-        } else {
-            let index = self.explore_index(total_step);
-            g.play(self.children[index].action);
-            let mut res = self.children[index].select(g, total_step);
-            res.insert(0, index);
-            res
+    impl GameTest for Morpion {
+        type Action = usize;
+
+        fn play(&mut self, play: usize) -> PlayRes {
+            self.board[play] = Some(self.next);
+            let won = LINES
+                .iter()
+                .any(|l| l.iter().all(|&i| self.board[i] == Some(self.next)));
+            self.next = 1 - self.next;
+            if won {
+                PlayRes::Win
+            } else if self.board.iter().all(|c| c.is_some()) {
+                PlayRes::Draw
+            } else {
+                PlayRes::Nothing
+            }
+        }
+
+        fn valid_actions(&self) -> Vec<usize> {
+            (0..9).filter(|&i| self.board[i].is_none()).collect()
+        }
+
+        fn current_player(&self) -> usize {
+            self.next
+        }
+
+        /// Completing a line is worth `1.0`, blocking one `0.8`.
+        fn action_prior(&self, &action: &usize) -> Option<f64> {
+            let completes = |player| {
+                LINES.iter().any(|l| {
+                    l.contains(&action)
+                        && l.iter()
+                            .all(|&i| i == action || self.board[i] == Some(player))
+                })
+            };
+            Some(if completes(self.next) {
+                1.0
+            } else if completes(1 - self.next) {
+                0.8
+            } else {
+                0.5
+            })
+        }
+
+        fn order_actions(&self, actions: &mut [usize]) {
+            // Center first, then corners.
+            actions.sort_by_key(|&a| (a != 4, a % 2));
+        }
+
+        fn state_hash(&self) -> Option<u64> {
+            Some(
+                self.board
+                    .iter()
+                    .fold(0, |h, c| 3 * h + c.map_or(0, |p| p as u64 + 1)),
+            )
+        }
+
+        fn max_reward(&self) -> Option<f64> {
+            Some(1.0)
+        }
+
+        /// `1.0` for the cells of the player to move, `-1.0` for the other.
+        fn encode(&self) -> Option<Vec<f32>> {
+            let cell = |c: &Option<usize>| match c {
+                Some(p) if *p == self.next => 1.0,
+                Some(_) => -1.0,
+                None => 0.0,
+            };
+            Some(self.board.iter().map(cell).collect())
+        }
+
+        fn action_index(&self, action: &usize) -> Option<usize> {
+            Some(*action)
+        }
+    }
+
+    fn best_move(moves: &[usize]) -> usize {
+        best_move_with(moves, Ucb1::default())
+    }
+
+    fn best_move_with<S: SelectionPolicy + Send + 'static>(moves: &[usize], selection: S) -> usize {
+        let mut g = Morpion::from_moves(moves);
+        let mut mcts = MCTS::new().with_selection(selection);
+        for _ in 0..2000 {
+            mcts.train(&mut g);
+        }
+        mcts.play_best_move(&mut g).unwrap()
+    }
+
+    #[test]
+    fn takes_the_win() {
+        // X: 0 1, O: 3 4, X to move.
+        assert_eq!(best_move(&[0, 3, 1, 4]), 2);
+    }
+
+    #[test]
+    fn blocks_the_opponent() {
+        // X: 0 1, O: 4, O to move.
+        assert_eq!(best_move(&[0, 4, 1]), 2);
+    }
+
+    #[test]
+    fn every_selection_policy_blocks() {
+        assert_eq!(best_move_with(&[0, 4, 1], Ucb1Tuned), 2);
+        assert_eq!(best_move_with(&[0, 4, 1], Puct::default()), 2);
+        assert_eq!(best_move_with(&[0, 4, 1], UcbV::default()), 2);
+        assert_eq!(best_move_with(&[0, 4, 1], Thompson), 2);
+    }
+
+    #[test]
+    fn search_is_send() {
+        fn assert_send<S: Send>(_: &S) {}
+        assert_send(
+            &MCTS::<Morpion>::new()
+                .with_selection(Thompson)
+                .with_rollout(WinningMove)
+                .with_seed(0),
+        );
+        assert_send(&MCTS::<Morpion>::new().with_evaluator(RolloutEvaluator { playouts: 1 }));
+    }
+
+    #[test]
+    fn rave() {
+        assert_eq!(Rave::Equivalence(100.0).beta(0, 10), 1.0);
+        assert_eq!(Rave::Equivalence(100.0).beta(100, 10), 0.5);
+        assert_eq!(Rave::MinimumMse(0.0).beta(10, 30), 0.75);
+        assert!(Rave::MinimumMse(0.1).beta(100, 30) < Rave::MinimumMse(0.1).beta(10, 30));
+
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new().with_rave(Rave::Equivalence(50.0));
+        for _ in 0..500 {
+            mcts.train(&mut g);
+        }
+        // Every playout through the root plays most of its children.
+        for c in mcts.tree.children(mcts.tree.root) {
+            assert!(c.amaf.plays > c.stats.plays);
+        }
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+    }
+
+    /// Stands for a trained model that is sure the next move is `best`.
+    struct Oracle {
+        best: usize,
+    }
+
+    impl Evaluator<Morpion> for Oracle {
+        fn evaluate(&self, g: &Morpion, actions: &[usize], _: &mut dyn RngCore) -> Evaluation {
+            Evaluation {
+                priors: actions
+                    .iter()
+                    .map(|&a| (a == self.best) as u8 as f64)
+                    .collect(),
+                value: share(g.players(), None, 1.0),
+            }
+        }
+    }
+
+    #[test]
+    fn evaluator() {
+        // X: 0 1, O: 4, O has to block on 2.
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new().with_seed(1).with_evaluator(Oracle { best: 2 });
+        for _ in 0..20 {
+            mcts.train(&mut g);
+        }
+        // One evaluation per iteration, no playouts.
+        assert_eq!(root(&mcts).stats.plays, 20);
+        let children = mcts.tree.children(mcts.tree.root);
+        let block = children.iter().find(|c| c.action == Some(2)).unwrap();
+        assert_eq!(block.prior, Some(1.0));
+        assert!(block.stats.plays >= 15);
+
+        let mut mcts = MCTS::new()
+            .with_seed(1)
+            .with_evaluator(RolloutEvaluator { playouts: 4 });
+        mcts.search(&mut g, &Budget::new().iterations(1000));
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+    }
+
+    /// Values every state as a draw and records the size of each batch.
+    #[derive(Default)]
+    struct Batches {
+        sizes: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Evaluator<Morpion> for Batches {
+        fn evaluate(&self, g: &Morpion, actions: &[usize], _: &mut dyn RngCore) -> Evaluation {
+            Evaluation {
+                priors: vec![1.0 / actions.len() as f64; actions.len()],
+                value: share(g.players(), None, 1.0),
+            }
+        }
+
+        fn evaluate_batch(
+            &self,
+            leaves: &[Leaf<Morpion>],
+            rng: &mut dyn RngCore,
+        ) -> Vec<Evaluation> {
+            self.sizes.lock().unwrap().push(leaves.len());
+            leaves
+                .iter()
+                .map(|l| self.evaluate(&l.state, &l.actions, rng))
+                .collect()
         }
     }
 
-    fn expand(&mut self, actions: Vec<usize>) {
-        for a in actions {
-            self.children.push(Tree {
-                children: Vec::new(),
-                plays: 0,
-                wins: 0,
-                action: a,
+    #[test]
+    fn batched_evaluation() {
+        let mut g = Morpion::from_moves(&[]);
+        let mut mcts = MCTS::new().with_seed(1).with_evaluator(Batches::default());
+        mcts.train(&mut g);
+
+        // Virtual losses send each selection to a different child.
+        assert_eq!(mcts.select_leaves(&g, 4), 4);
+        let mut leaves: Vec<NodeId> = mcts.pending().iter().map(|l| l.path[1]).collect();
+        leaves.sort();
+        leaves.dedup();
+        assert_eq!(leaves.len(), 4);
+        assert_eq!(root(&mcts).virtual_loss, 4);
+        let evaluations = mcts
+            .pending()
+            .iter()
+            .map(|_| Evaluation {
+                priors: vec![0.125; 8],
+                value: vec![1.0, 0.0],
             })
+            .collect();
+        mcts.back_up(evaluations);
+        assert!(mcts.pending().is_empty());
+        assert_eq!(root(&mcts).virtual_loss, 0);
+        assert_eq!(root(&mcts).stats.plays, 5);
+        assert_eq!(root(&mcts).stats.rewards[0], 4.5);
+
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let evaluator = Batches {
+            sizes: sizes.clone(),
+        };
+        let mut mcts = MCTS::new()
+            .with_seed(1)
+            .with_evaluator(evaluator)
+            .with_batch(8);
+        mcts.search(&mut g, &Budget::new().iterations(200));
+        assert_eq!(root(&mcts).stats.plays, 200);
+        let sizes = sizes.lock().unwrap();
+        // Leaves whose game is over are backed up without an evaluation.
+        assert!(sizes.iter().sum::<usize>() <= 200);
+        assert!(sizes.iter().all(|&n| n <= 8));
+        assert!(sizes.contains(&8));
+        assert!((0..mcts.tree.len()).all(|id| mcts.tree[id].virtual_loss == 0));
+
+        // X: 0 1, O: 4, O has to block on 2.
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new()
+            .with_seed(1)
+            .with_evaluator(RolloutEvaluator { playouts: 4 })
+            .with_batch(8);
+        mcts.search(&mut g, &Budget::new().iterations(1000));
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+    }
+
+    #[test]
+    fn self_play() {
+        let mut g = Morpion::from_moves(&[]);
+        let settings = SelfPlay {
+            budget: Budget::new().iterations(200),
+            temperature_moves: 2,
+            ..SelfPlay::default()
+        };
+        let mut mcts = MCTS::new().with_seed(1);
+        let records = mcts.self_play(&mut g, &settings);
+        assert!(records.len() >= 5 && records.len() <= 9);
+        assert_eq!(records[0].state, vec![0.0; 9]);
+        assert_eq!(records[0].visits.len(), 9);
+        for (i, r) in records.iter().enumerate() {
+            assert_eq!(r.player, i % 2);
+            let total: f32 = r.visits.iter().map(|v| v.1).sum();
+            assert!((total - 1.0).abs() < 1e-4);
+            assert_eq!(r.outcome, records[0].outcome);
+        }
+        assert!((records[0].outcome.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+
+        let mut writer = RecordWriter::new(Vec::new()).unwrap();
+        for r in &records {
+            writer.write(r).unwrap();
+        }
+        let mut bytes = writer.into_inner().unwrap();
+        assert_eq!(read_records(&bytes[..]).unwrap(), records);
+        assert!(read_records(&bytes[..bytes.len() - 1]).is_err());
+        bytes[8] = FORMAT_VERSION as u8 + 1;
+        let error = read_records(&bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // Noise makes every root prior a mix with uniform ones.
+        let mut g = Morpion::from_moves(&[]);
+        let mut mcts = MCTS::new().with_seed(1);
+        mcts.train(&mut g);
+        mcts.add_noise(Noise::default());
+        let priors: Vec<f64> = mcts
+            .tree
+            .children(mcts.tree.root)
+            .iter()
+            .map(|c| c.prior.unwrap())
+            .collect();
+        assert_eq!(priors.len(), 9);
+        assert!((priors.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(priors.iter().all(|&p| p >= 0.75 / 9.0));
+
+        // By default, moves are indexed by their position in valid_actions.
+        let g = Gamble {
+            heads: 0.5,
+            flipping: false,
+        };
+        assert_eq!(g.action_index(&Bet::Risky), Some(1));
+        assert_eq!(g.action_index(&Bet::Heads), None);
+    }
+
+    #[test]
+    fn progressive_widening() {
+        let widening = Widening { c: 1.0, alpha: 0.5 };
+        assert_eq!(widening.unlocked(0), 1);
+        assert_eq!(widening.unlocked(10), 4);
+
+        let mut g = Morpion::from_moves(&[]);
+        let mut mcts = MCTS::new().with_widening(widening);
+        mcts.train(&mut g);
+        let children = mcts.tree.children(mcts.tree.root);
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].action, Some(4));
+        assert_eq!(root(&mcts).locked, vec![7, 5, 3, 1, 8, 6, 2, 0]);
+
+        for _ in 0..20 {
+            mcts.train(&mut g);
+        }
+        let unlocked = widening.unlocked(root(&mcts).stats.plays);
+        assert!(root(&mcts).children.len() <= unlocked);
+        assert!(root(&mcts).children.len() > 1);
+
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new().with_widening(widening);
+        for _ in 0..2000 {
+            mcts.train(&mut g);
         }
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
     }
 
-    fn simulate<T: GameTest>(&mut self, g: &mut T) -> bool {
+    #[test]
+    fn heuristic_priors() {
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        assert_eq!(g.action_prior(&2), Some(0.8));
+        assert_eq!(g.action_prior(&3), Some(0.5));
+        assert_eq!(Priors::ProgressiveBias(1.0).bias(0.8, 3), 0.2);
+
+        let mut mcts = MCTS::new().with_priors(Priors::VirtualVisits(10));
+        mcts.train(&mut g);
+        for c in mcts.tree.children(mcts.tree.root) {
+            assert_eq!(c.stats.plays, 11);
+            let virtual_reward = 10.0 * c.prior.unwrap();
+            assert!(c.reward() >= virtual_reward && c.reward() <= virtual_reward + 1.0);
+        }
+
+        for priors in [Priors::VirtualVisits(10), Priors::ProgressiveBias(1.0)] {
+            let mut g = Morpion::from_moves(&[0, 4, 1]);
+            let mut mcts = MCTS::new().with_priors(priors);
+            for _ in 0..300 {
+                mcts.train(&mut g);
+            }
+            assert_eq!(mcts.play_best_move(&mut g), Some(2));
+        }
+    }
+
+    #[test]
+    fn winning_move_rollout() {
+        let g = Morpion::from_moves(&[0, 3, 1, 4]);
         let actions = g.valid_actions();
-        if actions.len() == 0 {
-            self.plays += 1;
-            return false;
+        let index = WinningMove.choose(&g, &actions, &mut StdRng::seed_from_u64(0));
+        assert_eq!(actions[index], 2);
+
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new().with_rollout(WinningMove);
+        for _ in 0..500 {
+            mcts.train(&mut g);
+        }
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+    }
+
+    #[test]
+    fn epsilon_greedy_rollout() {
+        let g = Morpion::from_moves(&[]);
+        let actions = g.valid_actions();
+        let center = EpsilonGreedy::new(
+            0.0,
+            |_: &Morpion, &a: &usize| {
+                if a == 4 {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+        );
+        let index = center.choose(&g, &actions, &mut StdRng::seed_from_u64(0));
+        assert_eq!(actions[index], 4);
+    }
+
+    fn root<T: GameTest>(mcts: &MCTS<T>) -> &Node<T::Action> {
+        &mcts.tree[mcts.tree.root]
+    }
+
+    fn size<A>(tree: &Tree<A>, id: NodeId) -> usize {
+        1 + tree[id]
+            .children
+            .iter()
+            .map(|&c| size(tree, c))
+            .sum::<usize>()
+    }
+
+    fn child(plays: u32, reward: f64) -> Node<usize> {
+        let mut stats = Stats::new(2);
+        stats.plays = plays;
+        stats.rewards[0] = reward;
+        Node::new(Some(0), 0, stats)
+    }
+
+    #[test]
+    fn final_move_strategies() {
+        let nodes = [
+            child(100, 60.0),
+            child(10, 9.0),
+            child(1, 1.0),
+            child(0, 0.0),
+        ];
+        let children: Vec<&Node<usize>> = nodes.iter().collect();
+        assert_eq!(FinalMove::Robust.index(&children), Some(0));
+        assert_eq!(FinalMove::Max.index(&children), Some(2));
+        assert_eq!(FinalMove::Secure(1.0).index(&children), Some(1));
+        assert_eq!(FinalMove::MaxRobust(0).index(&children), None);
+        assert_eq!(FinalMove::MaxRobust(0).index(&children[..2]), None);
+        assert_eq!(FinalMove::MaxRobust(0).index(&children[..1]), Some(0));
+        assert_eq!(FinalMove::Robust.index(&children[3..]), None);
+        assert_eq!(FinalMove::Max.index(&children[..0]), None);
+    }
+
+    #[test]
+    fn finished_game_has_no_best_move() {
+        // The board is full and the game drawn.
+        let mut g = Morpion::from_moves(&[0, 1, 2, 4, 3, 5, 7, 6, 8]);
+        let mut mcts = MCTS::new();
+        assert_eq!(mcts.play_best_move(&mut g), None);
+
+        // X: 0 1, O: 3 4, the opponent plays the winning 2.
+        let mut g = Morpion::from_moves(&[0, 3, 1, 4]);
+        let mut mcts = MCTS::new().with_final_move(FinalMove::MaxRobust(10));
+        for _ in 0..100 {
+            mcts.train(&mut g);
+        }
+        assert_eq!(mcts.apply_ext(&mut g, 2), Ok(()));
+        assert_eq!(mcts.play_best_move(&mut g), None);
+    }
+
+    #[test]
+    fn search_budgets() {
+        let mut g = Morpion::from_moves(&[]);
+        let mut mcts = MCTS::new();
+        let budget = Budget::new().iterations(10);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Iterations);
+        assert_eq!(root(&mcts).stats.plays, 9 + 8 * 9);
+        assert_eq!(mcts.nodes(), size(&mcts.tree, mcts.tree.root));
+
+        let budget = Budget::new().nodes(200).time(Duration::from_secs(60));
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Nodes);
+        assert!(mcts.nodes() >= 200);
+
+        let budget = Budget::new().memory(mcts.memory() * 2);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Memory);
+
+        let budget = Budget::new().time(Duration::from_millis(20));
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Time);
+        assert_eq!(mcts.search(&mut g, &Budget::new()), StopReason::Unbounded);
+    }
+
+    #[test]
+    fn apply_ext_reroots() {
+        let mut g = Morpion::from_moves(&[]);
+        let mut mcts = MCTS::new();
+        assert_eq!(mcts.apply_ext(&mut g, 4), Ok(()));
+        assert_eq!(root(&mcts).action, Some(4));
+        assert_eq!(root(&mcts).player, 0);
+        assert_eq!(root(&mcts).children.len(), 8);
+
+        for _ in 0..50 {
+            mcts.train(&mut g);
+        }
+        let child = mcts.tree.children(mcts.tree.root)[0];
+        let plays = child.stats.plays;
+        let action = child.action.unwrap();
+        assert_eq!(mcts.apply_ext(&mut g, action), Ok(()));
+        assert!(root(&mcts).stats.plays > plays);
+        assert_eq!(mcts.nodes(), size(&mcts.tree, mcts.tree.root));
+
+        assert_eq!(mcts.apply_ext(&mut g, 4), Err(IllegalAction(4)));
+        assert_eq!(root(&mcts).action, Some(action));
+    }
+
+    #[test]
+    fn same_seed_same_tree() {
+        let search = |seed: u64, moves: &[usize]| {
+            let mut g = Morpion::from_moves(moves);
+            let mut mcts = MCTS::new().with_seed(seed).with_selection(Thompson);
+            mcts.search(&mut g, &Budget::new().iterations(200));
+            let tree = format!("{:?}", mcts);
+            (tree, mcts.play_best_move(&mut g))
+        };
+        assert_eq!(search(7, &[]), search(7, &[]));
+        assert_eq!(search(7, &[0, 4]), search(7, &[0, 4]));
+        assert_ne!(search(7, &[]).0, search(8, &[]).0);
+
+        let mut g = Morpion::from_moves(&[]);
+        let mut a = MCTS::new().with_rng(StdRng::seed_from_u64(3));
+        let mut b = MCTS::new().with_seed(3);
+        for _ in 0..50 {
+            a.train(&mut g);
+            b.train(&mut g);
+        }
+        assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    }
+
+    #[test]
+    fn root_parallel() {
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let parallel = RootParallel::new(4, MCTS::new);
+        let budget = Budget::new().iterations(200);
+
+        let children = parallel.search(&g, &budget);
+        assert_eq!(children.len(), 6);
+        let plays: u32 = children.iter().map(|c| c.plays).sum();
+        assert!(plays > 4 * 200);
+
+        assert_eq!(parallel.play_best_move(&mut g, &budget), Some(2));
+        assert_eq!(g.board[2], Some(1));
+
+        // The board is full, the game is a draw.
+        let mut g = Morpion::from_moves(&[0, 1, 2, 4, 3, 5, 7, 6, 8]);
+        assert!(parallel.search(&g, &budget).is_empty());
+        assert_eq!(parallel.play_best_move(&mut g, &budget), None);
+    }
+
+    #[test]
+    fn tree_parallel() {
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut parallel = TreeParallel::new(4);
+        let budget = Budget::new().iterations(2000);
+        assert_eq!(parallel.play_best_move(&mut g), None);
+
+        assert_eq!(parallel.search(&g, &budget), StopReason::Iterations);
+        assert_eq!(parallel.plays(), 2000);
+        assert_eq!(parallel.play_best_move(&mut g), Some(2));
+        assert_eq!(g.board[2], Some(1));
+
+        assert_eq!(parallel.apply_ext(&mut g, 2), Err(IllegalAction(2)));
+        assert_eq!(parallel.apply_ext(&mut g, 3), Ok(()));
+        assert!(parallel.nodes() > 1);
+
+        // X: 0 1 3 8, O: 4 2 and O to move, 6 wins and ends the game.
+        assert_eq!(parallel.search(&g, &budget), StopReason::Iterations);
+        assert_eq!(parallel.apply_ext(&mut g, 6), Ok(()));
+        parallel.search(&g, &budget);
+        assert_eq!(parallel.play_best_move(&mut g), None);
+    }
+
+    /// Whether two nodes of `mcts` have the same children.
+    fn shares_children(mcts: &MCTS<Morpion>) -> bool {
+        let mut expanded: Vec<&Vec<NodeId>> = (0..mcts.tree.len())
+            .map(|id| &mcts.tree[id].children)
+            .filter(|c| !c.is_empty())
+            .collect();
+        let count = expanded.len();
+        expanded.sort();
+        expanded.dedup();
+        expanded.len() < count
+    }
+
+    #[test]
+    fn transpositions_share_nodes() {
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut tree = MCTS::new();
+        let mut dag = MCTS::new().with_transpositions();
+        for _ in 0..300 {
+            tree.train(&mut g);
+            dag.train(&mut g);
+        }
+        assert!(!shares_children(&tree));
+        assert!(shares_children(&dag));
+
+        assert_eq!(dag.play_best_move(&mut g), Some(2));
+    }
+
+    /// Child of `parent` reached by `action`.
+    fn child_by<T: GameTest>(mcts: &MCTS<T>, parent: NodeId, action: T::Action) -> NodeId {
+        let children = &mcts.tree[parent].children;
+        let action = Some(action);
+        *children
+            .iter()
+            .find(|&&c| mcts.tree[c].action == action)
+            .unwrap()
+    }
+
+    #[test]
+    fn transposed_values_are_shared() {
+        let mut g = Sticks { left: 4, next: 0 };
+        let mut mcts = MCTS::new().with_transpositions();
+        for _ in 0..300 {
+            mcts.train(&mut g);
         }
-        let indice = Uniform::from(0..actions.len()).sample(&mut rand::thread_rng());
-        let action = actions[indice];
-        match g.play(action) {
-            PlayRes::Nothing => self.simulate(g),
-            PlayRes::Win => {
-                self.plays += 1;
-                self.wins += 1;
-                true
+        // Taking 1 then 2 sticks or 2 then 1 leaves the same state, whose
+        // only move takes the last stick.
+        let root = mcts.tree.root;
+        let first = &mcts.tree[child_by(&mcts, child_by(&mcts, root, 1), 2)];
+        let second = &mcts.tree[child_by(&mcts, child_by(&mcts, root, 2), 1)];
+        assert_eq!(first.children, second.children);
+        assert!(first.stats.plays > 1 && second.stats.plays > 1);
+
+        // Both nodes got a playout of their own when they were created, and
+        // every later visit through either order reached the shared child.
+        let last = &mcts.tree[first.children[0]];
+        assert_eq!(last.stats.plays + 2, first.stats.plays + second.stats.plays);
+    }
+
+    #[test]
+    fn transposed_children_stay_shared() {
+        let mut g = Morpion::from_moves(&[]);
+        let mut dag = MCTS::new()
+            .with_transpositions()
+            .with_widening(Widening::default());
+        for _ in 0..3000 {
+            dag.train(&mut g);
+        }
+
+        // Every node reached by a transposition must see the children of
+        // the node expanded for its state, as of its last visit, so that
+        // what was backed up through one path is found through the other.
+        let tree = &dag.tree;
+        let table = tree.transpositions.as_ref().unwrap();
+        let mut transposed = 0;
+        let mut stack = vec![(tree.root, Vec::new())];
+        while let Some((id, moves)) = stack.pop() {
+            let hash = Morpion::from_moves(&moves).state_hash().unwrap();
+            match table.get(&hash) {
+                Some(&owner) if owner != id && !tree[id].children.is_empty() => {
+                    assert!(tree[owner].children.starts_with(&tree[id].children));
+                    transposed += 1;
+                }
+                _ => (),
             }
-            PlayRes::Loose => {
-                self.plays += 1;
-                false
+            if moves.len() < 4 {
+                for &c in &tree[id].children {
+                    let mut moves = moves.clone();
+                    moves.push(tree[c].action.unwrap());
+                    stack.push((c, moves));
+                }
             }
         }
+        assert!(transposed > 0);
     }
 
-    fn backprop(&mut self, path: &Vec<usize>, from: usize, plays: u32, wins: u32) {
-        self.plays += plays;
-        self.wins += wins;
-        if from < path.len() {
-            let index = self.best_child_index();
-            self.children[path[from]].backprop(path, from + 1, plays, wins)
+    #[test]
+    fn solver_proves_the_root() {
+        // X: 0 1, O: 3 4, X to move wins at 2.
+        let mut g = Morpion::from_moves(&[0, 3, 1, 4]);
+        let mut mcts = MCTS::new().with_solver();
+        let budget = Budget::new().iterations(1000);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Solved);
+        assert_eq!(mcts.solved(), Some(&[1.0, 0.0][..]));
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+
+        // X: 0 1, O: 4, O has to block and the game is a draw.
+        let mut g = Morpion::from_moves(&[0, 4, 1]);
+        let mut mcts = MCTS::new().with_solver();
+        let budget = Budget::new().iterations(100_000);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Solved);
+        assert_eq!(mcts.solved(), Some(&[0.5, 0.5][..]));
+        assert!(root(&mcts).stats.plays < 100_000);
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
+
+        // Without the solver the whole budget is spent.
+        let mut mcts = MCTS::new();
+        let budget = Budget::new().iterations(1000);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Iterations);
+        assert_eq!(mcts.solved(), None);
+    }
+
+    #[test]
+    fn solver_without_reward_bound() {
+        // The point proven at once must not solve the root before the five
+        // points of the other move are.
+        let mut g = Bonus { moves: 0 };
+        let mut mcts = MCTS::new().with_solver();
+        let budget = Budget::new().iterations(100);
+        assert_eq!(mcts.search(&mut g, &budget), StopReason::Solved);
+        assert_eq!(mcts.solved(), Some(&[5.0, 0.0][..]));
+        assert_eq!(mcts.play_best_move(&mut g), Some(1));
+    }
+
+    #[test]
+    fn solver_prefers_a_proven_best_child() {
+        let mut tree: Tree<usize> = Tree::new();
+        let root = tree.root;
+        let mut draw = child(10, 5.0);
+        draw.proven = Some(vec![0.5, 0.5]);
+        for node in [draw, child(40, 12.0)] {
+            let id = tree.add(node);
+            tree[root].children.push(id);
         }
+        assert_eq!(FinalMove::robust_index(&tree.children(root)), Some(1));
+        assert_eq!(tree.proven_index(root), Some(0));
+
+        let id = tree.add(child(40, 28.0));
+        tree[root].children.push(id);
+        assert_eq!(tree.proven_index(root), None);
     }
 
-    fn fmt(&self, i: usize) -> String {
-        if self.children.len() == 0 {
-            String::new()
-        } else {
-            self.children
-                .iter()
-                .fold(String::new(), |acc: String, c: &Tree| {
-                    format!(
-                        "{}{:2}:{:2}/{:2}--{}\n{}",
-                        acc,
-                        c.action,
-                        c.wins,
-                        c.plays,
-                        c.fmt(i + 1),
-                        String::from(" |        ").repeat(i)
-                    )
-                })
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    enum Bet {
+        Safe,
+        Risky,
+        Heads,
+        Tails,
+    }
+
+    /// Player 0 either takes 0.6 or bets on a coin landing heads with
+    /// probability `heads`.
+    #[derive(Clone)]
+    struct Gamble {
+        heads: f64,
+        flipping: bool,
+    }
+
+    impl GameTest for Gamble {
+        type Action = Bet;
+
+        fn play(&mut self, play: Bet) -> PlayRes {
+            match play {
+                Bet::Safe => PlayRes::Rewards(vec![0.6, 0.4]),
+                Bet::Risky => {
+                    self.flipping = true;
+                    PlayRes::Nothing
+                }
+                Bet::Heads => PlayRes::Win,
+                Bet::Tails => PlayRes::Loose,
+            }
+        }
+
+        fn valid_actions(&self) -> Vec<Bet> {
+            vec![Bet::Safe, Bet::Risky]
+        }
+
+        fn current_player(&self) -> usize {
+            0
+        }
+
+        fn chance_outcomes(&self) -> Vec<(Bet, f64)> {
+            if self.flipping {
+                vec![(Bet::Heads, self.heads), (Bet::Tails, 1.0 - self.heads)]
+            } else {
+                Vec::new()
+            }
         }
     }
-}
 
-pub struct MCTS {
-    tree: Tree,
-    tot_step: u32,
-}
+    #[test]
+    fn chance_nodes() {
+        for (heads, best) in [(0.3, Bet::Safe), (0.9, Bet::Risky)] {
+            let mut g = Gamble {
+                heads,
+                flipping: false,
+            };
+            let mut mcts = MCTS::new().with_solver().with_seed(1);
+            mcts.search(&mut g, &Budget::new().iterations(100));
 
-impl std::fmt::Debug for MCTS {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let res = format!("{}", self.tree.fmt(1));
-        write!(f, " @:{:2}/{:2}--{}", self.tree.wins, self.tree.plays, res)
+            let risky = mcts.tree.children(mcts.tree.root)[1];
+            assert_eq!(risky.action, Some(Bet::Risky));
+            let mean = risky.reward() / risky.stats.plays as f64;
+            assert!((mean - heads).abs() < 1e-9);
+            let solved = mcts.solved().unwrap();
+            assert!((solved[0] - heads.max(0.6)).abs() < 1e-9);
+            assert_eq!(mcts.play_best_move(&mut g), Some(best));
+        }
+
+        let mut g = Gamble {
+            heads: 0.5,
+            flipping: true,
+        };
+        let mut mcts = MCTS::new();
+        assert_eq!(mcts.play_best_move(&mut g), None);
+        assert_eq!(
+            mcts.apply_ext(&mut g, Bet::Safe),
+            Err(IllegalAction(Bet::Safe))
+        );
+        assert_eq!(mcts.apply_ext(&mut g, Bet::Heads), Ok(()));
     }
-}
 
-impl MCTS {
-    pub fn new() -> MCTS {
-        MCTS {
-            tree: Tree {
-                children: Vec::new(),
-                plays: 0,
-                wins: 0,
-                action: 0,
-            },
-            tot_step: 0,
+    #[test]
+    fn tree_parallel_chance_nodes() {
+        for (heads, best) in [(0.3, Bet::Safe), (0.9, Bet::Risky)] {
+            let mut g = Gamble {
+                heads,
+                flipping: false,
+            };
+            let mut mcts = TreeParallel::new(2).with_seed(1);
+            mcts.search(&g, &Budget::new().iterations(400));
+            assert_eq!(mcts.play_best_move(&mut g), Some(best));
         }
+
+        let mut g = Gamble {
+            heads: 0.5,
+            flipping: true,
+        };
+        let mut mcts = TreeParallel::new(2);
+        mcts.search(&g, &Budget::new().iterations(10));
+        assert_eq!(mcts.play_best_move(&mut g), None);
+        assert_eq!(
+            mcts.apply_ext(&mut g, Bet::Safe),
+            Err(IllegalAction(Bet::Safe))
+        );
+        assert_eq!(mcts.apply_ext(&mut g, Bet::Heads), Ok(()));
     }
 
-    pub fn train<T: GameTest + Clone>(&mut self, g: &mut T) {
-        let mut new_g: T = g.clone();
+    /// Two players hold three of the cards 0 to 7 each, the last two are
+    /// never dealt. The leader plays a card, then the other player, and the
+    /// highest card wins the trick and leads the next one. Rewards are the
+    /// share of the three tricks won.
+    #[derive(Clone)]
+    struct Tricks {
+        hands: [Vec<usize>; 2],
+        unseen: Vec<usize>,
+        led: Option<usize>,
+        next: usize,
+        won: [usize; 2],
+    }
+
+    impl GameTest for Tricks {
+        type Action = usize;
+
+        fn play(&mut self, play: usize) -> PlayRes {
+            self.hands[self.next].retain(|&c| c != play);
+            match self.led.take() {
+                None => {
+                    self.led = Some(play);
+                    self.next = 1 - self.next;
+                }
+                Some(led) => {
+                    if led > play {
+                        self.next = 1 - self.next;
+                    }
+                    self.won[self.next] += 1;
+                }
+            }
+            if self.hands[self.next].is_empty() {
+                PlayRes::Rewards(self.won.iter().map(|&w| w as f64 / 3.0).collect())
+            } else {
+                PlayRes::Nothing
+            }
+        }
 
-        let path = self.tree.select(&mut new_g, self.tot_step);
-        let leaf = self.tree.get_at(&path, 0, path.len());
+        fn valid_actions(&self) -> Vec<usize> {
+            self.hands[self.next].clone()
+        }
+
+        fn current_player(&self) -> usize {
+            self.next
+        }
 
-        leaf.expand(new_g.valid_actions());
+        fn determinize(&self, observer: usize, rng: &mut dyn RngCore) -> Option<Tricks> {
+            use rand::seq::SliceRandom;
+            let other = 1 - observer;
+            let mut pool = self.hands[other].clone();
+            pool.extend(&self.unseen);
+            pool.shuffle(rng);
+            let mut g = self.clone();
+            g.unseen = pool.split_off(self.hands[other].len());
+            g.hands[other] = pool;
+            Some(g)
+        }
+    }
 
-        let mut acc_win = 0;
-        let mut acc_play = 0;
+    #[test]
+    fn information_sets() {
+        let mut g = Tricks {
+            hands: [vec![1, 5, 7], vec![2, 4, 6]],
+            unseen: vec![0, 3],
+            led: None,
+            next: 0,
+            won: [0, 0],
+        };
+        let mut mcts = MCTS::new().with_seed(1).with_information_sets();
+        mcts.search(&mut g, &Budget::new().iterations(2000));
 
-        for l in &mut leaf.children {
-            if l.simulate(&mut new_g.clone()) == true {
-                acc_win += 1;
+        // Only the cards seen by player 0 are tried first, but the replies
+        // gather every card player 1 could hold.
+        let children = mcts.tree.children(mcts.tree.root);
+        let mut cards: Vec<_> = children.iter().map(|c| c.action.unwrap()).collect();
+        cards.sort();
+        assert_eq!(cards, vec![1, 5, 7]);
+        assert!(children.iter().any(|c| c.children.len() > 3));
+        for c in &children {
+            assert!(c.available >= c.stats.plays);
+            for &r in &c.children {
+                assert!(mcts.tree[r].available >= mcts.tree[r].stats.plays);
             }
-            acc_play += 1;
         }
+        let best = mcts.play_best_move(&mut g).unwrap();
+        assert!(cards.contains(&best));
+    }
+
+    /// Rock, paper, scissors as `0`, `1`, `2`, each beating the one before.
+    #[derive(Clone)]
+    struct Shifumi;
 
-        self.tree.backprop(&path, 0, acc_play, acc_win);
+    impl SimultaneousGame for Shifumi {
+        type Action = usize;
 
-        self.tot_step += 1;
+        fn valid_actions(&self, _player: usize) -> Vec<usize> {
+            vec![0, 1, 2]
+        }
+
+        fn play(&mut self, actions: &[usize]) -> PlayRes {
+            match (actions[0] + 3 - actions[1]) % 3 {
+                0 => PlayRes::Draw,
+                1 => PlayRes::Win,
+                _ => PlayRes::Loose,
+            }
+        }
     }
 
-    pub fn apply_ext<T: GameTest + Clone>(&mut self, g: &mut T, play: usize) {
-        self.update(
-            g,
-            self.tree
-                .children
-                .iter()
-                .position(|v| v.action == play)
-                .unwrap(),
-        )
+    /// Both players bid one of their cards `1` to `3` for the prizes `3`,
+    /// `2` then `1`, the highest bid takes the prize and ties discard it.
+    #[derive(Clone)]
+    struct Goofspiel {
+        hands: [Vec<usize>; 2],
+        prize: usize,
+        score: [usize; 2],
     }
 
-    fn update<T: GameTest + Clone>(&mut self, g: &mut T, play: usize) {
-        let mut new_tree = self.tree.children[play].clone();
-        std::mem::swap(&mut self.tree, &mut new_tree);
+    impl SimultaneousGame for Goofspiel {
+        type Action = usize;
 
-        g.play(self.tree.action);
+        fn valid_actions(&self, player: usize) -> Vec<usize> {
+            self.hands[player].clone()
+        }
 
-        self.train(g);
+        fn play(&mut self, actions: &[usize]) -> PlayRes {
+            for (hand, played) in self.hands.iter_mut().zip(actions) {
+                hand.retain(|c| c != played);
+            }
+            if actions[0] != actions[1] {
+                self.score[(actions[1] > actions[0]) as usize] += self.prize;
+            }
+            self.prize -= 1;
+            match (self.prize, self.score[0].cmp(&self.score[1])) {
+                (0, Ordering::Greater) => PlayRes::Win,
+                (0, Ordering::Less) => PlayRes::Loose,
+                (0, Ordering::Equal) => PlayRes::Draw,
+                _ => PlayRes::Nothing,
+            }
+        }
     }
 
-    pub fn play_best_move<T: GameTest + Clone>(&mut self, g: &mut T) {
-        self.train(g);
+    #[test]
+    fn simultaneous_moves() {
+        // Rock, paper, scissors has no pure equilibrium, Exp3 mixes evenly.
+        let mut mcts = SimultaneousMCTS::new()
+            .with_seed(1)
+            .with_bandit(Bandit::Exp3(0.1));
+        mcts.search(&Shifumi, &Budget::new().iterations(20_000));
+        for p in 0..2 {
+            let policy = mcts.policy(p);
+            assert_eq!(policy.len(), 3);
+            assert!(policy.iter().all(|&(_, prob)| prob > 0.2 && prob < 0.47));
+        }
+
+        // Bidding the 3 on the first prize never loses it, so player 0
+        // does it most often under decoupled UCT.
+        let mut g = Goofspiel {
+            hands: [vec![1, 2, 3], vec![1, 2, 3]],
+            prize: 3,
+            score: [0, 0],
+        };
+        let mut mcts = SimultaneousMCTS::new().with_seed(1);
+        mcts.search(&g, &Budget::new().iterations(5000));
+        let policy = mcts.policy(0);
+        let total: f64 = policy.iter().map(|p| p.1).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let best = policy.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        assert_eq!(best.unwrap().0, 3);
+        assert!(mcts.sample_action(1).is_some());
 
-        let best_index = self.tree.best_child_index();
-        self.update(g, best_index);
+        let nodes = mcts.nodes();
+        assert!(matches!(
+            mcts.apply_ext(&mut g, vec![3, 4]),
+            Err(IllegalAction(_))
+        ));
+        assert!(matches!(
+            mcts.apply_ext(&mut g, vec![3, 1]),
+            Ok(PlayRes::Nothing)
+        ));
+        assert!(mcts.nodes() > 1 && mcts.nodes() < nodes);
+        assert_eq!(mcts.policy(1).len(), 2);
+    }
+
+    #[test]
+    fn draw_is_half_a_win() {
+        // Only cell 8 is left and filling it draws the game.
+        let mut g = Morpion::from_moves(&[0, 1, 2, 4, 3, 5, 7, 6]);
+        let mut mcts = MCTS::new();
+        for _ in 0..10 {
+            mcts.train(&mut g);
+        }
+        let child = mcts.tree.children(mcts.tree.root)[0];
+        assert_eq!(child.action, Some(8));
+        assert_eq!(child.reward(), 0.5 * child.stats.plays as f64);
+    }
+
+    /// Three players take one or two sticks in turn, taking the last one wins.
+    #[derive(Clone)]
+    struct Sticks {
+        left: usize,
+        next: usize,
+    }
+
+    impl GameTest for Sticks {
+        type Action = usize;
+
+        fn play(&mut self, play: usize) -> PlayRes {
+            self.left -= play;
+            self.next = (self.next + 1) % 3;
+            if self.left == 0 {
+                PlayRes::Win
+            } else {
+                PlayRes::Nothing
+            }
+        }
+
+        fn valid_actions(&self) -> Vec<usize> {
+            (1..=self.left.min(2)).collect()
+        }
+
+        fn current_player(&self) -> usize {
+            self.next
+        }
+
+        fn players(&self) -> usize {
+            3
+        }
+
+        fn state_hash(&self) -> Option<u64> {
+            Some((3 * self.left + self.next) as u64)
+        }
+    }
+
+    /// The first player either takes one point and ends the game, or lets
+    /// the second player end it by handing over five.
+    #[derive(Clone)]
+    struct Bonus {
+        moves: usize,
+    }
+
+    impl GameTest for Bonus {
+        type Action = usize;
+
+        fn play(&mut self, play: usize) -> PlayRes {
+            self.moves += 1;
+            match (self.moves, play) {
+                (1, 0) => PlayRes::Rewards(vec![1.0, 0.0]),
+                (1, _) => PlayRes::Nothing,
+                _ => PlayRes::Rewards(vec![5.0, 0.0]),
+            }
+        }
+
+        fn valid_actions(&self) -> Vec<usize> {
+            if self.moves == 0 {
+                vec![0, 1]
+            } else {
+                vec![0]
+            }
+        }
+
+        fn current_player(&self) -> usize {
+            self.moves % 2
+        }
+    }
+
+    /// Both players count down from `left`, reaching zero is a draw.
+    #[derive(Clone)]
+    struct Countdown {
+        left: usize,
+        next: usize,
+    }
+
+    impl GameTest for Countdown {
+        type Action = usize;
+
+        fn play(&mut self, play: usize) -> PlayRes {
+            self.left -= play;
+            self.next = 1 - self.next;
+            if self.left == 0 {
+                PlayRes::Draw
+            } else {
+                PlayRes::Nothing
+            }
+        }
+
+        fn valid_actions(&self) -> Vec<usize> {
+            vec![1]
+        }
+
+        fn current_player(&self) -> usize {
+            self.next
+        }
+    }
+
+    #[test]
+    fn long_playouts_do_not_overflow() {
+        let mut g = Countdown {
+            left: 500_000,
+            next: 0,
+        };
+        let mut mcts = MCTS::new();
+        for _ in 0..3 {
+            mcts.train(&mut g);
+        }
+        assert_eq!(root(&mcts).stats.plays, 3);
+    }
+
+    #[test]
+    fn deep_trees_print() {
+        let mut mcts: MCTS<Countdown> = MCTS::new();
+        let mut parent = mcts.tree.root;
+        for _ in 0..3_000 {
+            let id = mcts.tree.add(child(1, 0.5));
+            mcts.tree[parent].children.push(id);
+            parent = id;
+        }
+        assert_eq!(format!("{:?}", mcts).lines().count(), 3_001);
+    }
+
+    #[test]
+    fn three_players() {
+        let mut g = Sticks { left: 2, next: 1 };
+        let mut mcts = MCTS::new();
+        for _ in 0..100 {
+            mcts.train(&mut g);
+        }
+        for c in mcts.tree.children(mcts.tree.root) {
+            assert_eq!(c.player, 1);
+            assert_eq!(c.stats.rewards.len(), 3);
+            assert_eq!(c.stats.rewards.iter().sum::<f64>(), c.stats.plays as f64);
+        }
+        assert_eq!(mcts.play_best_move(&mut g), Some(2));
     }
 }
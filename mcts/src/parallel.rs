@@ -0,0 +1,111 @@
+use crate::{Budget, FinalMove, GameTest, Node, MCTS};
+use std::collections::HashMap;
+use std::thread;
+
+/// Statistics of one move of the root, merged over every tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootChild<A> {
+    pub action: A,
+    pub plays: u32,
+    /// Accumulated reward of the player to move at the root.
+    pub reward: f64,
+}
+
+/// Root parallelisation: runs one independent tree per thread from the
+/// same state and merges the statistics of their root children before
+/// choosing a move. Trees are built by `new` and dropped after each search.
+pub struct RootParallel<F> {
+    threads: usize,
+    new: F,
+    final_move: FinalMove,
+}
+
+impl<T, F> RootParallel<F>
+where
+    T: GameTest + Clone + Send,
+    T::Action: Send,
+    F: Fn() -> MCTS<T> + Sync,
+{
+    pub fn new(threads: usize, new: F) -> RootParallel<F> {
+        RootParallel {
+            threads,
+            new,
+            final_move: FinalMove::default(),
+        }
+    }
+
+    /// Uses `final_move` on the merged statistics, `MaxRobust` never
+    /// searches further and falls back to `Robust`.
+    pub fn with_final_move(mut self, final_move: FinalMove) -> RootParallel<F> {
+        self.final_move = final_move;
+        self
+    }
+
+    fn merged(&self, g: &T, budget: &Budget) -> Vec<Node<T::Action>> {
+        let results: Vec<Vec<Node<T::Action>>> = thread::scope(|s| {
+            let handles: Vec<_> = (0..self.threads)
+                .map(|_| {
+                    let mut g = g.clone();
+                    s.spawn(move || {
+                        let mut mcts = (self.new)();
+                        mcts.search(&mut g, budget);
+                        mcts.tree
+                            .children(mcts.tree.root)
+                            .into_iter()
+                            .cloned()
+                            .collect()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut merged: Vec<Node<T::Action>> = Vec::new();
+        let mut index: HashMap<T::Action, usize> = HashMap::new();
+        for child in results.into_iter().flatten() {
+            let action = child.action.clone().unwrap();
+            match index.get(&action) {
+                Some(&i) => merged[i].stats.merge(&child.stats),
+                None => {
+                    index.insert(action, merged.len());
+                    let mut node = Node::new(child.action, child.player, child.stats);
+                    node.terminal = child.terminal;
+                    merged.push(node);
+                }
+            }
+        }
+        merged
+    }
+
+    /// Searches `g` with every thread within `budget` each, and returns the
+    /// merged statistics of the root children.
+    pub fn search(&self, g: &T, budget: &Budget) -> Vec<RootChild<T::Action>> {
+        self.merged(g, budget)
+            .into_iter()
+            .map(|n| RootChild {
+                reward: n.reward(),
+                plays: n.stats.plays,
+                action: n.action.unwrap(),
+            })
+            .collect()
+    }
+
+    /// Searches `g`, plays the best move on the merged statistics and
+    /// returns it. Returns `None` without playing when no tree has a move
+    /// at its root, such as once the game is over, or while a random event
+    /// is pending.
+    pub fn play_best_move(&self, g: &mut T, budget: &Budget) -> Option<T::Action> {
+        if !g.chance_outcomes().is_empty() {
+            return None;
+        }
+        let merged = self.merged(g, budget);
+        let children: Vec<&Node<T::Action>> = merged.iter().collect();
+        let index = match self.final_move.index(&children) {
+            Some(index) => index,
+            None => FinalMove::robust_index(&children)?,
+        };
+        let action = merged[index].action.clone().unwrap();
+        g.play(action.clone());
+        Some(action)
+    }
+}
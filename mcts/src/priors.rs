@@ -0,0 +1,44 @@
+use crate::tree::Stats;
+
+/// How the heuristic priors of `GameTest::action_prior` guide the search,
+/// see `MCTS::with_priors`. Either way the priors, normalised, are also the
+/// `ChildStats::prior` given to the selection policy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priors {
+    /// Seeds each new child with the given number of virtual visits, won in
+    /// proportion to its prior.
+    VirtualVisits(u32),
+    /// Adds `w * prior / (plays + 1)` to the score of each child, with `w`
+    /// the given weight, so that the bias fades as the child gets visited.
+    ProgressiveBias(f64),
+}
+
+impl Priors {
+    /// Statistics of a new child of `player` with the given `prior`.
+    pub(crate) fn seed(self, prior: f64, player: usize, players: usize) -> Stats {
+        let mut stats = Stats::new(players);
+        if let Priors::VirtualVisits(visits) = self {
+            let n = visits as f64;
+            for p in 0..players {
+                let reward = if p == player {
+                    prior
+                } else {
+                    (1.0 - prior) / (players - 1) as f64
+                };
+                stats.rewards[p] = reward * n;
+                stats.sq_rewards[p] = reward * reward * n;
+            }
+            stats.plays = visits;
+        }
+        stats
+    }
+
+    /// Bias added to the score of a child with the given `prior` after
+    /// `plays` visits.
+    pub(crate) fn bias(self, prior: f64, plays: u32) -> f64 {
+        match self {
+            Priors::VirtualVisits(_) => 0.0,
+            Priors::ProgressiveBias(w) => w * prior / (plays as f64 + 1.0),
+        }
+    }
+}
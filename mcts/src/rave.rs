@@ -0,0 +1,48 @@
+use crate::Node;
+
+/// Schedule of the weight `beta` given to the all-moves-as-first (AMAF)
+/// value of a child against its own value, see `MCTS::with_rave`. The
+/// child is valued `(1 - beta) * mean + beta * amaf_mean`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rave {
+    /// `beta = sqrt(k / (3 * plays + k))`, both values weigh the same once
+    /// the child was played the given `k` times.
+    Equivalence(f64),
+    /// `beta = amaf / (plays + amaf + 4 * b * b * plays * amaf)` with the
+    /// given bias `b` of the AMAF value, which minimises the mean squared
+    /// error of the blended value.
+    MinimumMse(f64),
+}
+
+impl Rave {
+    /// Weight of the AMAF value of a child played `plays` times with
+    /// `amaf_plays` AMAF updates.
+    pub fn beta(self, plays: u32, amaf_plays: u32) -> f64 {
+        let n = plays as f64;
+        let amaf = amaf_plays as f64;
+        match self {
+            Rave::Equivalence(k) => (k / (3.0 * n + k)).sqrt(),
+            Rave::MinimumMse(b) => {
+                if amaf_plays == 0 {
+                    0.0
+                } else {
+                    amaf / (n + amaf + 4.0 * b * b * n * amaf)
+                }
+            }
+        }
+    }
+
+    /// Accumulated reward of `child`, seen from the player choosing it, with
+    /// its mean replaced by the blended value.
+    pub(crate) fn reward<A>(self, child: &Node<A>) -> f64 {
+        let plays = child.stats.plays;
+        let amaf_plays = child.amaf.plays;
+        if plays == 0 || amaf_plays == 0 {
+            return child.reward();
+        }
+        let mean = child.reward() / plays as f64;
+        let amaf_mean = child.amaf_reward() / amaf_plays as f64;
+        let beta = self.beta(plays, amaf_plays);
+        ((1.0 - beta) * mean + beta * amaf_mean) * plays as f64
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{GameTest, PlayRes};
+use rand::distributions::{uniform::Uniform, Distribution};
+use rand::{Rng, RngCore};
+
+/// Chooses the moves played during the simulation phase.
+pub trait RolloutPolicy<T: GameTest> {
+    /// Returns the index in `actions` of the action to play in `g`.
+    fn choose(&self, g: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> usize;
+}
+
+fn uniform<A>(actions: &[A], rng: &mut dyn RngCore) -> usize {
+    Uniform::from(0..actions.len()).sample(rng)
+}
+
+/// Plays uniformly random moves, the default.
+#[derive(Default)]
+pub struct UniformRandom;
+
+impl<T: GameTest> RolloutPolicy<T> for UniformRandom {
+    fn choose(&self, _: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> usize {
+        uniform(actions, rng)
+    }
+}
+
+/// Plays the action with the best `heuristic` value, or a random one with
+/// probability `epsilon`.
+pub struct EpsilonGreedy<F> {
+    pub epsilon: f64,
+    pub heuristic: F,
+}
+
+impl<F> EpsilonGreedy<F> {
+    pub fn new(epsilon: f64, heuristic: F) -> EpsilonGreedy<F> {
+        EpsilonGreedy { epsilon, heuristic }
+    }
+}
+
+impl<T: GameTest, F: Fn(&T, &T::Action) -> f64> RolloutPolicy<T> for EpsilonGreedy<F> {
+    fn choose(&self, g: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> usize {
+        if rng.gen::<f64>() < self.epsilon {
+            return uniform(actions, rng);
+        }
+        let mut score = f64::NEG_INFINITY;
+        actions.iter().enumerate().fold(0, |acc, (index, a)| {
+            let new_score = (self.heuristic)(g, a);
+            if new_score > score {
+                score = new_score;
+                index
+            } else {
+                acc
+            }
+        })
+    }
+}
+
+/// Plays a move that wins on the spot if there is one, a random move
+/// otherwise.
+#[derive(Default)]
+pub struct WinningMove;
+
+impl<T: GameTest + Clone> RolloutPolicy<T> for WinningMove {
+    fn choose(&self, g: &T, actions: &[T::Action], rng: &mut dyn RngCore) -> usize {
+        actions
+            .iter()
+            .position(|a| matches!(g.clone().play(a.clone()), PlayRes::Win))
+            .unwrap_or_else(|| uniform(actions, rng))
+    }
+}
@@ -0,0 +1,124 @@
+use rand::distributions::{Beta, Distribution};
+use rand::RngCore;
+
+/// What a selection policy knows about a child, seen from the player who
+/// chooses it.
+pub struct ChildStats {
+    pub plays: u32,
+    /// Accumulated reward of the player choosing this child.
+    pub reward: f64,
+    /// Accumulated squared reward of the player choosing this child.
+    pub sq_reward: f64,
+    /// Prior probability of the child, uniform when the game gives none.
+    pub prior: f64,
+}
+
+impl ChildStats {
+    fn mean(&self) -> f64 {
+        self.reward / self.plays as f64
+    }
+
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        (self.sq_reward / self.plays as f64 - mean * mean).max(0.0)
+    }
+}
+
+/// Scores the children of a node during the selection phase, the child
+/// with the highest score is explored.
+pub trait SelectionPolicy {
+    fn score(&self, parent_plays: u32, child: &ChildStats, rng: &mut dyn RngCore) -> f64;
+}
+
+/// UCB1 with an exploration constant `c`, `sqrt(2)` by default.
+pub struct Ucb1 {
+    pub c: f64,
+}
+
+impl Default for Ucb1 {
+    fn default() -> Ucb1 {
+        Ucb1 { c: 2.0_f64.sqrt() }
+    }
+}
+
+impl SelectionPolicy for Ucb1 {
+    fn score(&self, parent_plays: u32, child: &ChildStats, _: &mut dyn RngCore) -> f64 {
+        if child.plays == 0 {
+            return f64::INFINITY;
+        }
+        let ln_n = (parent_plays as f64).ln();
+        child.mean() + self.c * (ln_n / child.plays as f64).sqrt()
+    }
+}
+
+/// UCB1-Tuned, which bounds the exploration term by the observed variance
+/// of the rewards.
+#[derive(Default)]
+pub struct Ucb1Tuned;
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn score(&self, parent_plays: u32, child: &ChildStats, _: &mut dyn RngCore) -> f64 {
+        if child.plays == 0 {
+            return f64::INFINITY;
+        }
+        let ln_n = (parent_plays as f64).ln();
+        let n = child.plays as f64;
+        let v = child.variance() + (2.0 * ln_n / n).sqrt();
+        child.mean() + (ln_n / n * v.min(0.25)).sqrt()
+    }
+}
+
+/// PUCT as used by AlphaZero, exploration is weighted by the child prior.
+/// Unvisited children are valued at `0.0`.
+pub struct Puct {
+    pub c: f64,
+}
+
+impl Default for Puct {
+    fn default() -> Puct {
+        Puct { c: 1.5 }
+    }
+}
+
+impl SelectionPolicy for Puct {
+    fn score(&self, parent_plays: u32, child: &ChildStats, _: &mut dyn RngCore) -> f64 {
+        let q = if child.plays == 0 { 0.0 } else { child.mean() };
+        q + self.c * child.prior * (parent_plays as f64).sqrt() / (1.0 + child.plays as f64)
+    }
+}
+
+/// UCB-V from Audibert, Munos and Szepesvári, with rewards in `[0, 1]` and
+/// the exploration function `zeta * ln(n)`.
+pub struct UcbV {
+    pub c: f64,
+    pub zeta: f64,
+}
+
+impl Default for UcbV {
+    fn default() -> UcbV {
+        UcbV { c: 1.0, zeta: 1.2 }
+    }
+}
+
+impl SelectionPolicy for UcbV {
+    fn score(&self, parent_plays: u32, child: &ChildStats, _: &mut dyn RngCore) -> f64 {
+        if child.plays == 0 {
+            return f64::INFINITY;
+        }
+        let e = self.zeta * (parent_plays as f64).ln();
+        let n = child.plays as f64;
+        child.mean() + (2.0 * child.variance() * e / n).sqrt() + self.c * 3.0 * e / n
+    }
+}
+
+/// Thompson sampling, scores are drawn from a Beta posterior on the mean
+/// reward of each child.
+#[derive(Default)]
+pub struct Thompson;
+
+impl SelectionPolicy for Thompson {
+    fn score(&self, _: u32, child: &ChildStats, rng: &mut dyn RngCore) -> f64 {
+        let losses = (child.plays as f64 - child.reward).max(0.0);
+        Beta::new(1.0 + child.reward, 1.0 + losses).sample(rng)
+    }
+}
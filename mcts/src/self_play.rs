@@ -0,0 +1,183 @@
+use crate::Budget;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 8] = b"MCTSPLAY";
+
+/// Version of the record format, bumped on every change to it.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Dirichlet noise mixed into the priors of the root children before each
+/// search of a self-play game, as `(1 - fraction) * prior + fraction *
+/// noise`, so that every move gets tried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Noise {
+    /// Concentration of the noise, lower values put it on fewer moves.
+    pub alpha: f64,
+    pub fraction: f64,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise {
+            alpha: 0.3,
+            fraction: 0.25,
+        }
+    }
+}
+
+/// Settings of `MCTS::self_play`.
+#[derive(Clone, Debug)]
+pub struct SelfPlay {
+    /// Search before each move.
+    pub budget: Budget,
+    /// During the first `temperature_moves` moves of a game, each move is
+    /// drawn with a probability proportional to `visits ^ (1 /
+    /// temperature)`. The most visited move is played after them, or with
+    /// a temperature of `0.0`.
+    pub temperature: f64,
+    pub temperature_moves: u32,
+    /// Noise on the priors of the root children, `None` by default. UCB1
+    /// ignores priors: it only changes the search with an evaluator, PUCT
+    /// or `MCTS::with_priors`.
+    pub noise: Option<Noise>,
+}
+
+impl Default for SelfPlay {
+    fn default() -> SelfPlay {
+        SelfPlay {
+            budget: Budget::new().iterations(800),
+            temperature: 1.0,
+            temperature_moves: 30,
+            noise: None,
+        }
+    }
+}
+
+/// One position of a self-play game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub player: usize,
+    /// Encoded state, empty for games without `GameTest::encode`.
+    pub state: Vec<f32>,
+    /// Share of the root visits of each move, by action index. Moves without
+    /// a `GameTest::action_index` are left out.
+    pub visits: Vec<(usize, f32)>,
+    /// Final reward of each player.
+    pub outcome: Vec<f32>,
+}
+
+/// Writes the records of self-play games to a file, all numbers
+/// little-endian:
+///
+/// ```text
+/// header  magic       8 bytes, "MCTSPLAY"
+///         version     u32, FORMAT_VERSION
+/// record  player      u32, player to move
+///         state       u32 length, then f32 values of GameTest::encode
+///         visits      u32 length, then (u32 action index, f32 share of the
+///                     root visits) pairs, by GameTest::action_index
+///         outcome     u32 length, then f32 final reward of each player
+/// ```
+///
+/// Records follow the header until the end of the file, which can hold any
+/// number of games. `read_records` loads them back.
+pub struct RecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Starts a file in `writer`. Appending to an existing file does not
+    /// need a new header, use `append` then.
+    pub fn new(mut writer: W) -> io::Result<RecordWriter<W>> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        Ok(RecordWriter { writer })
+    }
+
+    /// Writes records after the ones already in `writer`.
+    pub fn append(writer: W) -> RecordWriter<W> {
+        RecordWriter { writer }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let w = &mut self.writer;
+        w.write_all(&(record.player as u32).to_le_bytes())?;
+        write_f32s(w, &record.state)?;
+        w.write_all(&(record.visits.len() as u32).to_le_bytes())?;
+        for &(action, share) in &record.visits {
+            w.write_all(&(action as u32).to_le_bytes())?;
+            w.write_all(&share.to_le_bytes())?;
+        }
+        write_f32s(w, &record.outcome)
+    }
+
+    /// Flushes and returns the inner writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_f32s<W: Write>(w: &mut W, values: &[f32]) -> io::Result<()> {
+    w.write_all(&(values.len() as u32).to_le_bytes())?;
+    for v in values {
+        w.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(r)?))
+}
+
+fn read_f32s<R: Read>(r: &mut R) -> io::Result<Vec<f32>> {
+    let len = read_u32(r)?;
+    (0..len).map(|_| read_f32(r)).collect()
+}
+
+/// Reads back every record of a file written by `RecordWriter`. Fails with
+/// `InvalidData` on another format or version.
+pub fn read_records<R: Read>(mut reader: R) -> io::Result<Vec<Record>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a self-play record file"));
+    }
+    if read_u32(&mut reader)? != FORMAT_VERSION {
+        return Err(invalid("unsupported self-play record version"));
+    }
+
+    let mut records = Vec::new();
+    loop {
+        // The end of the file may only come between records.
+        let mut first = [0; 1];
+        if reader.read(&mut first)? == 0 {
+            return Ok(records);
+        }
+        let mut rest = [0; 3];
+        reader.read_exact(&mut rest)?;
+        let player = u32::from_le_bytes([first[0], rest[0], rest[1], rest[2]]) as usize;
+        let state = read_f32s(&mut reader)?;
+        let len = read_u32(&mut reader)?;
+        let visits = (0..len)
+            .map(|_| Ok((read_u32(&mut reader)? as usize, read_f32(&mut reader)?)))
+            .collect::<io::Result<_>>()?;
+        let outcome = read_f32s(&mut reader)?;
+        records.push(Record {
+            player,
+            state,
+            visits,
+            outcome,
+        });
+    }
+}
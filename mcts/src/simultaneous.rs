@@ -0,0 +1,395 @@
+use crate::tree::sample;
+use crate::{share, Budget, IllegalAction, PlayRes, StopReason};
+use rand::rngs::StdRng;
+use rand::{FromEntropy, Rng, RngCore, SeedableRng};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Instant;
+
+/// A game where every player chooses an action at each turn, without
+/// seeing the choices of the others. A player without a decision to make
+/// has a single action, such as passing.
+pub trait SimultaneousGame: Clone {
+    type Action: Clone + Debug + Eq;
+
+    fn players(&self) -> usize {
+        2
+    }
+
+    /// Actions `player` can choose from this turn. The game is a draw when
+    /// a player has none.
+    fn valid_actions(&self, player: usize) -> Vec<Self::Action>;
+
+    /// Plays `actions`, indexed by player, all at once. `Win` and `Loose`
+    /// are seen from player `0`.
+    fn play(&mut self, actions: &[Self::Action]) -> PlayRes;
+}
+
+/// How each player picks its action in a node of `SimultaneousMCTS`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bandit {
+    /// Decoupled UCT: UCB1 on the rewards of the player alone, with the
+    /// given exploration constant. Untried actions come first.
+    Ucb1(f64),
+    /// Exp3 with the given exploration rate `gamma`, which samples actions
+    /// and so suits games without a pure equilibrium.
+    Exp3(f64),
+}
+
+impl Default for Bandit {
+    fn default() -> Bandit {
+        Bandit::Ucb1(2.0_f64.sqrt())
+    }
+}
+
+/// Statistics of the actions of one player in one node.
+struct Arms<A> {
+    actions: Vec<A>,
+    plays: Vec<u32>,
+    rewards: Vec<f64>,
+    /// Sums of the importance weighted rewards used by Exp3.
+    estimates: Vec<f64>,
+}
+
+impl<A> Arms<A> {
+    fn new(actions: Vec<A>) -> Arms<A> {
+        let n = actions.len();
+        Arms {
+            actions,
+            plays: vec![0; n],
+            rewards: vec![0.0; n],
+            estimates: vec![0.0; n],
+        }
+    }
+
+    /// Exp3 probability of each action.
+    fn exp3(&self, gamma: f64) -> Vec<f64> {
+        let k = self.actions.len() as f64;
+        let eta = gamma / k;
+        let max = self
+            .estimates
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = self
+            .estimates
+            .iter()
+            .map(|e| (eta * (e - max)).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        weights
+            .iter()
+            .map(|w| (1.0 - gamma) * w / total + gamma / k)
+            .collect()
+    }
+
+    /// Index of the action to explore with the probability it was chosen
+    /// with, `1.0` for deterministic choices.
+    fn choose(&self, bandit: Bandit, plays: u32, rng: &mut dyn RngCore) -> (usize, f64) {
+        match bandit {
+            Bandit::Ucb1(c) => {
+                let ln_n = (plays as f64).ln();
+                let mut score = f64::NEG_INFINITY;
+                let index = (0..self.actions.len()).fold(0, |acc, i| {
+                    let new_score = match self.plays[i] {
+                        0 => f64::INFINITY,
+                        n => self.rewards[i] / n as f64 + c * (ln_n / n as f64).sqrt(),
+                    };
+                    if new_score > score {
+                        score = new_score;
+                        i
+                    } else {
+                        acc
+                    }
+                });
+                (index, 1.0)
+            }
+            Bandit::Exp3(gamma) => {
+                let probabilities = self.exp3(gamma);
+                let index = sample(probabilities.iter().cloned(), rng);
+                (index, probabilities[index])
+            }
+        }
+    }
+
+    fn push(&mut self, (index, probability): (usize, f64), reward: f64) {
+        self.plays[index] += 1;
+        self.rewards[index] += reward;
+        self.estimates[index] += reward / probability;
+    }
+
+    /// Mixed strategy given by the visits. With Exp3 the visits due to
+    /// exploration alone are removed first.
+    fn policy(&self, bandit: Bandit) -> Vec<f64> {
+        let plays: Vec<f64> = self.plays.iter().map(|&n| n as f64).collect();
+        let total: f64 = plays.iter().sum();
+        let mut weights = plays.clone();
+        if let Bandit::Exp3(gamma) = bandit {
+            let noise = gamma * total / self.actions.len() as f64;
+            weights = plays.iter().map(|n| (n - noise).max(0.0)).collect();
+        }
+        let mut sum: f64 = weights.iter().sum();
+        if sum == 0.0 {
+            weights = plays;
+            sum = total;
+        }
+        if sum == 0.0 {
+            return vec![1.0 / self.actions.len() as f64; self.actions.len()];
+        }
+        weights.iter().map(|w| w / sum).collect()
+    }
+}
+
+/// A node of the joint tree, each child follows one joint action.
+struct JointNode<A> {
+    plays: u32,
+    /// One set of arms per player, empty until the node is first visited.
+    arms: Vec<Arms<A>>,
+    /// Children by the index of the action of every player.
+    children: HashMap<Vec<usize>, usize>,
+}
+
+impl<A> JointNode<A> {
+    fn new() -> JointNode<A> {
+        JointNode {
+            plays: 0,
+            arms: Vec::new(),
+            children: HashMap::new(),
+        }
+    }
+}
+
+/// Simultaneous-move MCTS: each node keeps a separate bandit per player
+/// over their own actions, and the joint action they pick leads to the
+/// child. The final policy of a player is mixed, see `policy`.
+pub struct SimultaneousMCTS<T: SimultaneousGame> {
+    nodes: Vec<JointNode<T::Action>>,
+    root: usize,
+    bandit: Bandit,
+    rng: Box<dyn RngCore + Send>,
+}
+
+impl<T: SimultaneousGame> Default for SimultaneousMCTS<T> {
+    fn default() -> SimultaneousMCTS<T> {
+        SimultaneousMCTS::new()
+    }
+}
+
+impl<T: SimultaneousGame> SimultaneousMCTS<T> {
+    pub fn new() -> SimultaneousMCTS<T> {
+        SimultaneousMCTS {
+            nodes: vec![JointNode::new()],
+            root: 0,
+            bandit: Bandit::default(),
+            rng: Box::new(StdRng::from_entropy()),
+        }
+    }
+
+    pub fn with_bandit(mut self, bandit: Bandit) -> SimultaneousMCTS<T> {
+        self.bandit = bandit;
+        self
+    }
+
+    /// Seeds the random generator of the search, see `MCTS::with_seed`.
+    pub fn with_seed(mut self, seed: u64) -> SimultaneousMCTS<T> {
+        self.rng = Box::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Plays random joint actions until the game ends.
+    fn simulate(g: &mut T, rng: &mut dyn RngCore) -> Vec<f64> {
+        loop {
+            let mut actions = Vec::new();
+            for p in 0..g.players() {
+                let mut valid = g.valid_actions(p);
+                if valid.is_empty() {
+                    return share(g.players(), None, 1.0);
+                }
+                actions.push(valid.swap_remove(rng.gen_range(0, valid.len())));
+            }
+            if let Some(rewards) = Self::play_out(g, &actions) {
+                return rewards;
+            }
+        }
+    }
+
+    fn play_out(g: &mut T, actions: &[T::Action]) -> Option<Vec<f64>> {
+        let players = g.players();
+        match g.play(actions) {
+            PlayRes::Nothing => None,
+            PlayRes::Win => {
+                let mut rewards = vec![0.0; players];
+                rewards[0] = 1.0;
+                Some(rewards)
+            }
+            PlayRes::Loose => Some(share(players, Some(0), 1.0)),
+            PlayRes::Draw => Some(share(players, None, 1.0)),
+            PlayRes::Rewards(rewards) => Some(rewards),
+        }
+    }
+
+    /// One iteration of the search: selection down to the first new joint
+    /// action, which gets a child, then simulation and backpropagation.
+    pub fn train(&mut self, g: &T) {
+        let mut g = g.clone();
+        let mut path: Vec<(usize, Vec<(usize, f64)>)> = Vec::new();
+        let mut id = self.root;
+        let rewards = loop {
+            if self.nodes[id].arms.is_empty() {
+                self.nodes[id].arms = (0..g.players())
+                    .map(|p| Arms::new(g.valid_actions(p)))
+                    .collect();
+            }
+            let (bandit, rng) = (self.bandit, &mut *self.rng);
+            let node = &self.nodes[id];
+            if node.arms.iter().any(|a| a.actions.is_empty()) {
+                break share(g.players(), None, 1.0);
+            }
+
+            let choices: Vec<(usize, f64)> = node
+                .arms
+                .iter()
+                .map(|a| a.choose(bandit, node.plays, rng))
+                .collect();
+            let joint: Vec<usize> = choices.iter().map(|c| c.0).collect();
+            let actions: Vec<T::Action> = joint
+                .iter()
+                .enumerate()
+                .map(|(p, &i)| node.arms[p].actions[i].clone())
+                .collect();
+            let child = node.children.get(&joint).cloned();
+            path.push((id, choices));
+
+            let terminal = Self::play_out(&mut g, &actions);
+            match (child, terminal) {
+                (_, Some(rewards)) => break rewards,
+                (Some(child), None) => id = child,
+                (None, None) => {
+                    let child = self.nodes.len();
+                    self.nodes[id].children.insert(joint, child);
+                    self.nodes.push(JointNode::new());
+                    break Self::simulate(&mut g, &mut *self.rng);
+                }
+            }
+        };
+
+        for (id, choices) in path {
+            let node = &mut self.nodes[id];
+            node.plays += 1;
+            for (p, choice) in choices.into_iter().enumerate() {
+                node.arms[p].push(choice, rewards[p]);
+            }
+        }
+    }
+
+    /// Searches `g` until one of the limits of `budget` is reached, and
+    /// returns which one.
+    pub fn search(&mut self, g: &T, budget: &Budget) -> StopReason {
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            if let Some(reason) = budget.reached(iterations, start, self.nodes(), self.memory()) {
+                return reason;
+            }
+            self.train(g);
+            iterations += 1;
+        }
+    }
+
+    /// Number of nodes in the tree.
+    pub fn nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Approximate memory used by the tree, in bytes.
+    pub fn memory(&self) -> usize {
+        self.nodes.len() * std::mem::size_of::<JointNode<T::Action>>()
+    }
+
+    /// Mixed strategy of `player` at the root: each of its actions with the
+    /// probability to play it. Empty before the first search.
+    pub fn policy(&self, player: usize) -> Vec<(T::Action, f64)> {
+        match self.nodes[self.root].arms.get(player) {
+            Some(arms) => arms
+                .actions
+                .iter()
+                .cloned()
+                .zip(arms.policy(self.bandit))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Draws the action of `player` from its `policy`.
+    pub fn sample_action(&mut self, player: usize) -> Option<T::Action> {
+        let mut policy = self.policy(player);
+        if policy.is_empty() {
+            return None;
+        }
+        let index = sample(policy.iter().map(|p| p.1), &mut *self.rng);
+        Some(policy.swap_remove(index).0)
+    }
+
+    /// Plays `actions`, indexed by player, on `g` and moves the root of the
+    /// tree to them, keeping the subtree already explored when there is one.
+    pub fn apply_ext(
+        &mut self,
+        g: &mut T,
+        actions: Vec<T::Action>,
+    ) -> Result<PlayRes, IllegalAction<Vec<T::Action>>> {
+        if actions.len() != g.players() {
+            return Err(IllegalAction(actions));
+        }
+        let mut joint = Vec::new();
+        for (p, a) in actions.iter().enumerate() {
+            match g.valid_actions(p).iter().position(|v| v == a) {
+                Some(i) => joint.push(i),
+                None => return Err(IllegalAction(actions)),
+            }
+        }
+
+        let root = &self.nodes[self.root];
+        let child = match root.arms.is_empty() {
+            true => None,
+            false => root.children.get(&joint).cloned(),
+        };
+        match child {
+            Some(child) => self.reroot(child),
+            None => self.nodes = vec![JointNode::new()],
+        }
+        self.root = 0;
+        Ok(g.play(&actions))
+    }
+
+    /// Keeps only the subtree of `id`, which becomes the node `0`.
+    fn reroot(&mut self, id: usize) {
+        let mut old: Vec<Option<JointNode<T::Action>>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        // Old index, new index of the parent and joint action from it.
+        let mut stack = vec![(id, 0, Vec::new())];
+        while let Some((id, parent, joint)) = stack.pop() {
+            let mut node = old[id].take().unwrap();
+            let new_id = self.nodes.len();
+            if new_id > 0 {
+                self.nodes[parent].children.insert(joint, new_id);
+            }
+            for (joint, child) in node.children.drain() {
+                stack.push((child, new_id, joint));
+            }
+            self.nodes.push(node);
+        }
+    }
+}
+
+impl<T: SimultaneousGame> Debug for SimultaneousMCTS<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SimultaneousMCTS {{ plays: {}, nodes: {} }}",
+            self.nodes[self.root].plays,
+            self.nodes.len()
+        )
+    }
+}
@@ -0,0 +1,799 @@
+use crate::{
+    share, ChildStats, GameTest, PlayRes, Priors, Rave, Rewards, RolloutPolicy, SelectionPolicy,
+    Widening,
+};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::RngCore;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+
+/// Index of a node in the arena of a `Tree`.
+pub(crate) type NodeId = usize;
+
+/// Accumulated results of one or more playouts.
+#[derive(Clone, Default)]
+pub(crate) struct Stats {
+    pub(crate) plays: u32,
+    /// Accumulated reward of each player.
+    pub(crate) rewards: Rewards,
+    /// Accumulated squared reward of each player.
+    pub(crate) sq_rewards: Rewards,
+}
+
+impl Stats {
+    pub(crate) fn new(players: usize) -> Stats {
+        Stats {
+            plays: 0,
+            rewards: vec![0.0; players],
+            sq_rewards: vec![0.0; players],
+        }
+    }
+
+    pub(crate) fn push(&mut self, rewards: &[f64]) {
+        self.resize(rewards.len());
+        self.plays += 1;
+        for (p, r) in rewards.iter().enumerate() {
+            self.rewards[p] += r;
+            self.sq_rewards[p] += r * r;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &Stats) {
+        self.resize(other.rewards.len());
+        self.plays += other.plays;
+        for (p, r) in other.rewards.iter().enumerate() {
+            self.rewards[p] += r;
+            self.sq_rewards[p] += other.sq_rewards[p];
+        }
+    }
+
+    fn resize(&mut self, players: usize) {
+        if self.rewards.len() < players {
+            self.rewards.resize(players, 0.0);
+            self.sq_rewards.resize(players, 0.0);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Node<A> {
+    pub(crate) children: Vec<NodeId>,
+    pub(crate) stats: Stats,
+    /// The action leading to this node, `None` for a root never played.
+    pub(crate) action: Option<A>,
+    /// The player who played `action` to reach this node.
+    pub(crate) player: usize,
+    /// Final rewards if `action` ended the game.
+    pub(crate) terminal: Option<Rewards>,
+    /// Exact rewards under perfect play once the subtree is solved.
+    pub(crate) proven: Option<Rewards>,
+    /// Actions not unlocked yet by progressive widening, the next one last.
+    pub(crate) locked: Vec<A>,
+    /// Heuristic value of `action` given by the game.
+    pub(crate) prior: Option<f64>,
+    /// Probability of `action` when it is the outcome of a random event.
+    pub(crate) probability: Option<f64>,
+    /// Number of selections in the parent where `action` was legal, which
+    /// stand for the visits of the parent under information sets.
+    pub(crate) available: u32,
+    /// Pending evaluations below this node, each counted as a lost visit
+    /// by the selection, see `MCTS::select_leaves`.
+    pub(crate) virtual_loss: u32,
+    /// Results of the playouts where `player` played `action` after the
+    /// parent of this node, whenever that was.
+    pub(crate) amaf: Stats,
+}
+
+impl<A> Node<A> {
+    pub(crate) fn new(action: Option<A>, player: usize, stats: Stats) -> Node<A> {
+        Node {
+            children: Vec::new(),
+            stats,
+            action,
+            player,
+            terminal: None,
+            proven: None,
+            locked: Vec::new(),
+            prior: None,
+            probability: None,
+            available: 0,
+            virtual_loss: 0,
+            amaf: Stats::default(),
+        }
+    }
+
+    /// Accumulated reward of the player who moved into this node.
+    pub(crate) fn reward(&self) -> f64 {
+        self.stats.rewards.get(self.player).cloned().unwrap_or(0.0)
+    }
+
+    /// Accumulated AMAF reward of the player who moved into this node.
+    pub(crate) fn amaf_reward(&self) -> f64 {
+        self.amaf.rewards.get(self.player).cloned().unwrap_or(0.0)
+    }
+
+    /// Whether the node is solved and lost for the player who moved into it.
+    pub(crate) fn proven_loss(&self) -> bool {
+        self.proven
+            .as_ref()
+            .is_some_and(|r| r.get(self.player).cloned().unwrap_or(0.0) <= 0.0)
+    }
+
+    pub(crate) fn child_stats(&self, prior: f64) -> ChildStats {
+        ChildStats {
+            plays: self.stats.plays,
+            reward: self.reward(),
+            sq_reward: self
+                .stats
+                .sq_rewards
+                .get(self.player)
+                .cloned()
+                .unwrap_or(0.0),
+            prior,
+        }
+    }
+}
+
+/// The search tree, stored as a flat arena of nodes linked by index. Slots
+/// of nodes that are no longer reachable from the root are recycled.
+///
+/// With a transposition table, nodes reaching a state already expanded
+/// share its children, which turns the tree into a DAG.
+pub(crate) struct Tree<A> {
+    nodes: Vec<Node<A>>,
+    free: Vec<NodeId>,
+    pub(crate) root: NodeId,
+    /// Expanded node of each state hash.
+    pub(crate) transpositions: Option<HashMap<u64, NodeId>>,
+    /// Blends the AMAF statistics into selection when set.
+    pub(crate) rave: Option<Rave>,
+    /// Unlocks the children of a node gradually when set.
+    pub(crate) widening: Option<Widening>,
+    /// Proves the nodes whose value is known when set.
+    pub(crate) solver: bool,
+    /// Guides the search with the priors of the game when set.
+    pub(crate) priors: Option<Priors>,
+    /// Nodes stand for information sets, see `select_determinized`.
+    pub(crate) information_sets: bool,
+}
+
+impl<A> Index<NodeId> for Tree<A> {
+    type Output = Node<A>;
+
+    fn index(&self, id: NodeId) -> &Node<A> {
+        &self.nodes[id]
+    }
+}
+
+impl<A> IndexMut<NodeId> for Tree<A> {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node<A> {
+        &mut self.nodes[id]
+    }
+}
+
+/// Index of an outcome drawn according to `probabilities`.
+pub(crate) fn sample<I: IntoIterator<Item = f64>>(
+    probabilities: I,
+    rng: &mut dyn RngCore,
+) -> usize {
+    WeightedIndex::new(probabilities).unwrap().sample(rng)
+}
+
+impl<A: Clone + Debug> Tree<A> {
+    pub(crate) fn new() -> Tree<A> {
+        Tree {
+            nodes: vec![Node::new(None, 0, Stats::default())],
+            free: Vec::new(),
+            root: 0,
+            transpositions: None,
+            rave: None,
+            widening: None,
+            solver: false,
+            priors: None,
+            information_sets: false,
+        }
+    }
+
+    /// Number of live nodes.
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    /// Approximate memory used by the arena, in bytes.
+    pub(crate) fn memory(&self) -> usize {
+        let players = self.nodes[self.root].stats.rewards.len();
+        self.nodes.capacity() * std::mem::size_of::<Node<A>>()
+            + self.len()
+                * (4 * players * std::mem::size_of::<f64>() + std::mem::size_of::<NodeId>())
+    }
+
+    pub(crate) fn add(&mut self, node: Node<A>) -> NodeId {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn children(&self, id: NodeId) -> Vec<&Node<A>> {
+        self.nodes[id]
+            .children
+            .iter()
+            .map(|&c| &self.nodes[c])
+            .collect()
+    }
+
+    fn explore_index(
+        &self,
+        id: NodeId,
+        policy: &dyn SelectionPolicy,
+        rng: &mut dyn RngCore,
+    ) -> NodeId {
+        let node = &self.nodes[id];
+        if self.is_chance(id) {
+            let probabilities = node
+                .children
+                .iter()
+                .map(|&c| self.nodes[c].probability.unwrap());
+            return node.children[sample(probabilities, rng)];
+        }
+        // Visits of the state, shared children included, at least one so
+        // that PUCT follows the priors of children never visited.
+        let plays = node
+            .children
+            .iter()
+            .map(|&c| self.nodes[c].stats.plays + self.nodes[c].virtual_loss)
+            .sum::<u32>()
+            .max(1);
+        // Solved subtrees are not explored any more, unless they all are.
+        let unproven: Vec<NodeId> = node
+            .children
+            .iter()
+            .cloned()
+            .filter(|&c| self.nodes[c].proven.is_none())
+            .collect();
+        let candidates = if unproven.is_empty() {
+            &node.children
+        } else {
+            &unproven
+        };
+        self.best_of(&node.children, candidates, &|_| plays, policy, rng)
+    }
+
+    /// The child of `candidates` with the best score. The priors of
+    /// `siblings` are normalised together and `parent_plays` gives the
+    /// visits of the parent as seen by each child.
+    fn best_of(
+        &self,
+        siblings: &[NodeId],
+        candidates: &[NodeId],
+        parent_plays: &dyn Fn(&Node<A>) -> u32,
+        policy: &dyn SelectionPolicy,
+        rng: &mut dyn RngCore,
+    ) -> NodeId {
+        let uniform = 1.0 / siblings.len() as f64;
+        let total: f64 = siblings.iter().filter_map(|&c| self.nodes[c].prior).sum();
+
+        let mut score = f64::NEG_INFINITY;
+        candidates.iter().fold(candidates[0], |acc, &c| {
+            let child = &self.nodes[c];
+            let prior = match child.prior {
+                Some(prior) if total > 0.0 => prior / total,
+                _ => uniform,
+            };
+            let mut stats = child.child_stats(prior);
+            if let Some(rave) = self.rave {
+                stats.reward = rave.reward(child);
+            }
+            stats.plays += child.virtual_loss;
+            let mut new_score = policy.score(parent_plays(child), &stats, rng);
+            if let (Some(priors), Some(prior)) = (self.priors, child.prior) {
+                new_score += priors.bias(prior, child.stats.plays);
+            }
+            if new_score > score {
+                score = new_score;
+                c
+            } else {
+                acc
+            }
+        })
+    }
+
+    /// Walks down from the root playing the chosen actions on `g`, returns
+    /// the path from the root to the reached leaf.
+    pub(crate) fn select<T: GameTest<Action = A>>(
+        &mut self,
+        g: &mut T,
+        policy: &dyn SelectionPolicy,
+        rng: &mut dyn RngCore,
+    ) -> Vec<NodeId> {
+        let mut path = vec![self.root];
+        let mut id = self.root;
+        loop {
+            if self.nodes[id].proven.is_some() {
+                return path;
+            }
+            self.transpose(id, g, &path);
+            if self.nodes[id].children.is_empty() || self.can_widen(id) {
+                return path;
+            }
+            id = self.explore_index(id, policy, rng);
+            g.play(self.nodes[id].action.clone().unwrap());
+            path.push(id);
+        }
+    }
+
+    /// Selection under information sets, where `g` is one determinization
+    /// of the game: only the children legal in `g` are candidates. Returns
+    /// the path to the first node missing one of the legal actions, with
+    /// the final rewards if the game ended on the way.
+    pub(crate) fn select_determinized<T: GameTest<Action = A>>(
+        &mut self,
+        g: &mut T,
+        policy: &dyn SelectionPolicy,
+        rng: &mut dyn RngCore,
+    ) -> (Vec<NodeId>, Option<Rewards>)
+    where
+        A: PartialEq,
+    {
+        let mut path = vec![self.root];
+        let mut id = self.root;
+        loop {
+            let mut outcomes = g.chance_outcomes();
+            let actions = if outcomes.is_empty() {
+                g.valid_actions()
+            } else {
+                let indice = sample(outcomes.iter().map(|o| o.1), rng);
+                vec![outcomes.swap_remove(indice).0]
+            };
+            let legal: Vec<NodeId> = self.nodes[id]
+                .children
+                .iter()
+                .cloned()
+                .filter(|&c| actions.contains(self.nodes[c].action.as_ref().unwrap()))
+                .collect();
+            if actions.is_empty() || legal.len() < actions.len() {
+                return (path, None);
+            }
+
+            for &c in &legal {
+                self.nodes[c].available += 1;
+            }
+            id = self.best_of(&legal, &legal, &|c| c.available, policy, rng);
+            path.push(id);
+            if let Some(rewards) = Tree::play_out(g, self.nodes[id].action.clone().unwrap()) {
+                return (path, Some(rewards));
+            }
+        }
+    }
+
+    /// The node holding the children of the state of `g` reached at `id`:
+    /// the node first expanded for that state, unless it is on `path` which
+    /// would make a cycle, `id` itself otherwise.
+    pub(crate) fn owner<T: GameTest<Action = A>>(
+        &self,
+        id: NodeId,
+        g: &T,
+        path: &[NodeId],
+    ) -> NodeId {
+        if self.nodes[id].terminal.is_some() {
+            return id;
+        }
+        let shared = match &self.transpositions {
+            Some(table) => g.state_hash().and_then(|hash| table.get(&hash).cloned()),
+            None => None,
+        };
+        shared.filter(|s| !path.contains(s)).unwrap_or(id)
+    }
+
+    /// Copies the children of the node holding them for the state of `g`
+    /// to `id`, see `owner`. Done on every visit, as that node keeps
+    /// unlocking children with progressive widening.
+    pub(crate) fn transpose<T: GameTest<Action = A>>(
+        &mut self,
+        id: NodeId,
+        g: &T,
+        path: &[NodeId],
+    ) {
+        let owner = self.owner(id, g, path);
+        if owner != id {
+            self.nodes[id].children = self.nodes[owner].children.clone();
+            self.nodes[id].locked = self.nodes[owner].locked.clone();
+        }
+    }
+
+    /// Records `id` as the expanded node of the state of `g`.
+    pub(crate) fn insert<T: GameTest<Action = A>>(&mut self, id: NodeId, g: &T) {
+        if let (Some(table), Some(hash)) = (&mut self.transpositions, g.state_hash()) {
+            table.entry(hash).or_insert(id);
+        }
+    }
+
+    /// Whether the children of `id` are the outcomes of a random event.
+    pub(crate) fn is_chance(&self, id: NodeId) -> bool {
+        self.nodes[id]
+            .children
+            .first()
+            .is_some_and(|&c| self.nodes[c].probability.is_some())
+    }
+
+    /// Whether the visits of `id` allow one more of its children.
+    fn can_widen(&self, id: NodeId) -> bool {
+        let node = &self.nodes[id];
+        match self.widening {
+            Some(widening) if !node.locked.is_empty() => {
+                node.children.len() < widening.unlocked(node.stats.plays)
+            }
+            _ => false,
+        }
+    }
+
+    /// Creates the children of `id`, in the state of `g`, and returns them.
+    /// With progressive widening, the first visit only creates the ones its
+    /// visits allow in the order given by `g`, the next ones unlock a single
+    /// child. Under information sets, adds the actions of `g` missing from
+    /// the children.
+    pub(crate) fn expand<T: GameTest<Action = A>>(&mut self, id: NodeId, g: &T) -> Vec<NodeId>
+    where
+        A: PartialEq,
+    {
+        let existing: Vec<A> = match self.information_sets {
+            true => self
+                .children(id)
+                .iter()
+                .filter_map(|c| c.action.clone())
+                .collect(),
+            false => Vec::new(),
+        };
+        let node = &mut self.nodes[id];
+        let outcomes = g.chance_outcomes();
+        let mut actions: Vec<(A, Option<f64>)> = if !outcomes.is_empty() {
+            outcomes.into_iter().map(|(a, p)| (a, Some(p))).collect()
+        } else if !node.locked.is_empty() {
+            node.locked.pop().map(|a| (a, None)).into_iter().collect()
+        } else {
+            let mut actions = g.valid_actions();
+            if let Some(widening) = self.widening {
+                g.order_actions(&mut actions);
+                let unlocked = widening.unlocked(node.stats.plays).min(actions.len());
+                node.locked = actions.split_off(unlocked);
+                node.locked.reverse();
+            }
+            actions.into_iter().map(|a| (a, None)).collect()
+        };
+        actions.retain(|(a, _)| !existing.contains(a));
+
+        let player = g.current_player();
+        let players = g.players();
+        actions
+            .into_iter()
+            .map(|(a, probability)| {
+                let prior = match probability {
+                    Some(_) => None,
+                    None => self.priors.and_then(|_| g.action_prior(&a)),
+                };
+                let stats = match (self.priors, prior) {
+                    (Some(priors), Some(prior)) => priors.seed(prior, player, players),
+                    _ => Stats::new(players),
+                };
+                let mut node = Node::new(Some(a), player, stats);
+                node.prior = prior;
+                node.probability = probability;
+                let child = self.add(node);
+                self.nodes[id].children.push(child);
+                child
+            })
+            .collect()
+    }
+
+    /// Plays `action` for the player to move and returns the final rewards
+    /// if the game is over.
+    pub(crate) fn play_out<T: GameTest<Action = A>>(g: &mut T, action: A) -> Option<Rewards> {
+        let player = g.current_player();
+        let players = g.players();
+        match g.play(action) {
+            PlayRes::Nothing => None,
+            PlayRes::Win => {
+                let mut rewards = vec![0.0; players];
+                rewards[player] = 1.0;
+                Some(rewards)
+            }
+            PlayRes::Loose => Some(share(players, Some(player), 1.0)),
+            PlayRes::Draw => Some(share(players, None, 1.0)),
+            PlayRes::Rewards(rewards) => Some(rewards),
+        }
+    }
+
+    /// Plays moves chosen by `policy`, and random outcomes drawn according
+    /// to their probabilities, until the game ends and returns the final
+    /// rewards. Running out of actions is a draw. Each move is pushed to
+    /// `played` with its player when given.
+    pub(crate) fn simulate<T: GameTest<Action = A>>(
+        g: &mut T,
+        policy: &dyn RolloutPolicy<T>,
+        rng: &mut dyn RngCore,
+        mut played: Option<&mut Vec<(usize, A)>>,
+    ) -> Rewards {
+        loop {
+            let mut outcomes = g.chance_outcomes();
+            if !outcomes.is_empty() {
+                let indice = sample(outcomes.iter().map(|o| o.1), rng);
+                match Tree::play_out(g, outcomes.swap_remove(indice).0) {
+                    Some(rewards) => return rewards,
+                    None => continue,
+                }
+            }
+            let actions = g.valid_actions();
+            if actions.is_empty() {
+                return share(g.players(), None, 1.0);
+            }
+            let indice = policy.choose(g, &actions, rng);
+            if let Some(played) = played.as_mut() {
+                played.push((g.current_player(), actions[indice].clone()));
+            }
+            if let Some(rewards) = Tree::play_out(g, actions[indice].clone()) {
+                return rewards;
+            }
+        }
+    }
+
+    /// Adds `stats` to every node of `path`, from the leaf up. The rewards
+    /// of a chance node are then the expectation of the ones of its
+    /// outcomes.
+    pub(crate) fn backprop(&mut self, path: &[NodeId], stats: &Stats) {
+        for &id in path.iter().rev() {
+            self.nodes[id].stats.merge(stats);
+            if self.is_chance(id) {
+                self.expect(id);
+            }
+        }
+    }
+
+    /// Sets the mean rewards of the chance node `id` to the expectation of
+    /// the ones of its outcomes visited so far.
+    fn expect(&mut self, id: NodeId) {
+        let players = self.nodes[id].stats.rewards.len();
+        let mut rewards = vec![0.0; players];
+        let mut sq_rewards = vec![0.0; players];
+        let mut total = 0.0;
+        for &c in &self.nodes[id].children {
+            let child = &self.nodes[c];
+            if child.stats.plays == 0 {
+                continue;
+            }
+            let p = child.probability.unwrap();
+            let plays = child.stats.plays as f64;
+            total += p;
+            for k in 0..players.min(child.stats.rewards.len()) {
+                rewards[k] += p * child.stats.rewards[k] / plays;
+                sq_rewards[k] += p * child.stats.sq_rewards[k] / plays;
+            }
+        }
+        if total > 0.0 {
+            let node = &mut self.nodes[id];
+            let plays = node.stats.plays as f64;
+            node.stats.rewards = rewards.iter().map(|r| r / total * plays).collect();
+            node.stats.sq_rewards = sq_rewards.iter().map(|r| r / total * plays).collect();
+        }
+    }
+
+    /// Moves the root to `id` and recycles, in one sweep, every node that is
+    /// no longer reachable from it.
+    pub(crate) fn reroot(&mut self, id: NodeId) {
+        self.root = id;
+
+        let mut live = vec![false; self.nodes.len()];
+        let mut stack = vec![id];
+        while let Some(n) = stack.pop() {
+            if !live[n] {
+                live[n] = true;
+                stack.extend(&self.nodes[n].children);
+            }
+        }
+
+        self.free.clear();
+        for (n, &l) in live.iter().enumerate() {
+            if !l {
+                self.nodes[n] = Node::new(None, 0, Stats::default());
+                self.free.push(n);
+            }
+        }
+        if let Some(table) = &mut self.transpositions {
+            table.retain(|_, id| live[*id]);
+        }
+    }
+
+    /// Index of the child of `id` proven to be its best move: the one with
+    /// the proven rewards of `id` once it is solved, otherwise the best
+    /// solved child if its exact reward is at least the mean reward of
+    /// every visited child not solved yet. Solved children are no longer
+    /// visited, so their play counts say nothing about their value.
+    pub(crate) fn proven_index(&self, id: NodeId) -> Option<usize> {
+        let node = &self.nodes[id];
+        if let Some(proven) = &node.proven {
+            return node
+                .children
+                .iter()
+                .position(|&c| self.nodes[c].proven.as_ref() == Some(proven));
+        }
+        let (index, best) = node
+            .children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &c)| {
+                let child = &self.nodes[c];
+                let reward = child.proven.as_ref()?.get(child.player).cloned();
+                Some((i, reward.unwrap_or(0.0)))
+            })
+            .fold(None, |acc: Option<(usize, f64)>, (i, r)| match acc {
+                Some((_, best)) if best >= r => acc,
+                _ => Some((i, r)),
+            })?;
+        node.children
+            .iter()
+            .map(|&c| &self.nodes[c])
+            .filter(|c| c.proven.is_none() && c.stats.plays > 0)
+            .all(|c| c.reward() / c.stats.plays as f64 <= best)
+            .then_some(index)
+    }
+
+    /// Children of `id` with their index, leaving out the ones proven lost
+    /// unless they all are.
+    pub(crate) fn candidates(&self, id: NodeId) -> (Vec<usize>, Vec<&Node<A>>) {
+        let children = self.children(id);
+        let (indices, candidates): (Vec<usize>, Vec<&Node<A>>) = children
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.proven_loss())
+            .unzip();
+        if candidates.is_empty() {
+            ((0..children.len()).collect(), children)
+        } else {
+            (indices, candidates)
+        }
+    }
+
+    /// Exact rewards of `id` if it is solved: the rewards of a terminal
+    /// node, of a child giving `max_reward` to the player to move, or of the
+    /// best child for them once all are solved (max^n).
+    fn proven_value(&self, id: NodeId, max_reward: Option<f64>) -> Option<Rewards> {
+        let node = &self.nodes[id];
+        if node.terminal.is_some() {
+            return node.terminal.clone();
+        }
+        if node.children.is_empty() {
+            return None;
+        }
+        if self.is_chance(id) {
+            return self.proven_expectation(id);
+        }
+        let mut best: Option<(f64, &Rewards)> = None;
+        let mut solved = node.locked.is_empty();
+        for &c in &node.children {
+            let child = &self.nodes[c];
+            match &child.proven {
+                Some(rewards) => {
+                    let reward = rewards.get(child.player).cloned().unwrap_or(0.0);
+                    if max_reward.is_some_and(|max| reward >= max) {
+                        return Some(rewards.clone());
+                    }
+                    if best.is_none_or(|(r, _)| reward > r) {
+                        best = Some((reward, rewards));
+                    }
+                }
+                None => solved = false,
+            }
+        }
+        if solved {
+            best.map(|(_, rewards)| rewards.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Expectation of the proven rewards of the outcomes of the chance node
+    /// `id`, once they are all solved.
+    fn proven_expectation(&self, id: NodeId) -> Option<Rewards> {
+        let mut expectation: Rewards = Vec::new();
+        for &c in &self.nodes[id].children {
+            let child = &self.nodes[c];
+            let rewards = child.proven.as_ref()?;
+            expectation.resize(rewards.len().max(expectation.len()), 0.0);
+            for (e, r) in expectation.iter_mut().zip(rewards) {
+                *e += child.probability.unwrap() * r;
+            }
+        }
+        Some(expectation)
+    }
+
+    /// Proves the nodes of `path` that are now solved, from the leaf up,
+    /// when the solver is on. `max_reward` is the bound of `GameTest`.
+    pub(crate) fn prove(&mut self, path: &[NodeId], max_reward: Option<f64>) {
+        if !self.solver {
+            return;
+        }
+        for &id in path.iter().rev() {
+            if self.nodes[id].proven.is_some() {
+                continue;
+            }
+            match self.proven_value(id, max_reward) {
+                Some(rewards) => self.nodes[id].proven = Some(rewards),
+                None => return,
+            }
+        }
+    }
+
+    /// Backs up one playout in the AMAF statistics of the children of every
+    /// node of `path`, `played` being the moves made after its leaf.
+    pub(crate) fn backprop_amaf(&mut self, path: &[NodeId], played: &[(usize, A)], rewards: &[f64])
+    where
+        A: Eq + Hash,
+    {
+        let mut seen: HashSet<(usize, A)> = played.iter().cloned().collect();
+        for &id in path.iter().rev() {
+            for i in 0..self.nodes[id].children.len() {
+                let c = self.nodes[id].children[i];
+                let child = &mut self.nodes[c];
+                let key = (child.player, child.action.clone().unwrap());
+                if seen.contains(&key) {
+                    child.amaf.push(rewards);
+                }
+            }
+            let node = &self.nodes[id];
+            if let Some(action) = &node.action {
+                seen.insert((node.player, action.clone()));
+            }
+        }
+    }
+
+    /// Children of `id` and their subtrees, one line per child indented by
+    /// depth starting at `i`. Walks the tree with a stack, as deep trees
+    /// would overflow the call stack.
+    pub(crate) fn fmt(&self, id: NodeId, i: usize) -> String {
+        enum Step {
+            Child(NodeId, usize),
+            Close(usize),
+        }
+
+        let mut res = String::new();
+        let mut stack: Vec<Step> = self.nodes[id]
+            .children
+            .iter()
+            .rev()
+            .map(|&c| Step::Child(c, i))
+            .collect();
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Child(c, depth) => {
+                    let child = &self.nodes[c];
+                    res.push_str(&format!(
+                        "{:?}:{:2}/{:2}--",
+                        child.action.as_ref().unwrap(),
+                        child.reward(),
+                        child.stats.plays
+                    ));
+                    stack.push(Step::Close(depth));
+                    stack.extend(
+                        child
+                            .children
+                            .iter()
+                            .rev()
+                            .map(|&g| Step::Child(g, depth + 1)),
+                    );
+                }
+                Step::Close(depth) => {
+                    res.push('\n');
+                    res.push_str(&" |        ".repeat(depth));
+                }
+            }
+        }
+        res
+    }
+}
@@ -0,0 +1,469 @@
+use crate::tree::{sample, Stats, Tree};
+use crate::{
+    is_legal, share, Budget, FinalMove, GameTest, IllegalAction, Node, Rewards, RolloutPolicy,
+    SelectionPolicy, StopReason, Ucb1, UniformRandom,
+};
+use rand::rngs::StdRng;
+use rand::{FromEntropy, SeedableRng};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Instant;
+
+fn add_f64(a: &AtomicU64, v: f64) {
+    let _ = a.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        Some((f64::from_bits(bits) + v).to_bits())
+    });
+}
+
+fn load_f64(a: &AtomicU64) -> f64 {
+    f64::from_bits(a.load(Ordering::Relaxed))
+}
+
+/// A node of the shared tree. Statistics are atomic counters and children
+/// are created once, by the first thread to expand the node, then only
+/// ever read.
+struct SharedNode<A> {
+    action: Option<A>,
+    player: usize,
+    plays: AtomicU32,
+    /// Threads currently searching below this node.
+    virtual_loss: AtomicU32,
+    rewards: Vec<AtomicU64>,
+    sq_rewards: Vec<AtomicU64>,
+    /// Probability of `action` when it is the outcome of a random event.
+    probability: Option<f64>,
+    children: OnceLock<Vec<SharedNode<A>>>,
+}
+
+/// Whether `children` are the outcomes of a random event.
+fn is_chance<A>(children: &[SharedNode<A>]) -> bool {
+    children.first().is_some_and(|c| c.probability.is_some())
+}
+
+impl<A: Clone> SharedNode<A> {
+    fn new(action: Option<A>, player: usize, players: usize) -> SharedNode<A> {
+        SharedNode {
+            action,
+            player,
+            plays: AtomicU32::new(0),
+            virtual_loss: AtomicU32::new(0),
+            rewards: (0..players).map(|_| AtomicU64::new(0)).collect(),
+            sq_rewards: (0..players).map(|_| AtomicU64::new(0)).collect(),
+            probability: None,
+            children: OnceLock::new(),
+        }
+    }
+
+    /// Snapshot of the statistics, with `virtual_loss` extra plays counted
+    /// as lost for the player who moved into this node. The rewards of a
+    /// chance node are the expectation of the ones of its visited outcomes.
+    fn snapshot(&self, virtual_loss: u32) -> Node<A> {
+        let plays = self.plays.load(Ordering::Relaxed);
+        let mut stats = Stats {
+            plays: plays + virtual_loss,
+            rewards: self.rewards.iter().map(load_f64).collect(),
+            sq_rewards: self.sq_rewards.iter().map(load_f64).collect(),
+        };
+        if let Some(outcomes) = self.children.get().filter(|c| is_chance(c)) {
+            let players = stats.rewards.len();
+            let mut rewards = vec![0.0; players];
+            let mut sq_rewards = vec![0.0; players];
+            let mut total = 0.0;
+            for c in outcomes {
+                let n = c.plays.load(Ordering::Relaxed);
+                if n == 0 {
+                    continue;
+                }
+                let p = c.probability.unwrap();
+                total += p;
+                for k in 0..players {
+                    rewards[k] += p * load_f64(&c.rewards[k]) / n as f64;
+                    sq_rewards[k] += p * load_f64(&c.sq_rewards[k]) / n as f64;
+                }
+            }
+            if total > 0.0 {
+                let scale = plays as f64 / total;
+                stats.rewards = rewards.iter().map(|r| r * scale).collect();
+                stats.sq_rewards = sq_rewards.iter().map(|r| r * scale).collect();
+            }
+        }
+        Node::new(self.action.clone(), self.player, stats)
+    }
+
+    fn push(&self, rewards: &[f64]) {
+        self.plays.fetch_add(1, Ordering::Relaxed);
+        for (p, r) in rewards.iter().enumerate() {
+            add_f64(&self.rewards[p], *r);
+            add_f64(&self.sq_rewards[p], r * r);
+        }
+    }
+
+    fn size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(n) = stack.pop() {
+            size += 1;
+            if let Some(children) = n.children.get() {
+                stack.extend(children);
+            }
+        }
+        size
+    }
+}
+
+impl<A> Drop for SharedNode<A> {
+    /// Frees the subtree with an explicit stack, dropping the children of
+    /// every node recursively would overflow on deep trees.
+    fn drop(&mut self) {
+        let mut stack: Vec<SharedNode<A>> = self.children.take().unwrap_or_default();
+        while let Some(mut node) = stack.pop() {
+            if let Some(children) = node.children.take() {
+                stack.extend(children);
+            }
+        }
+    }
+}
+
+/// Tree parallelisation: many threads run the usual select, expand,
+/// simulate and backprop phases on a single shared tree. Every thread
+/// that walks through a node adds a virtual loss to it until its result is
+/// backed up, so that concurrent threads spread over different branches.
+///
+/// No lock is held during a search: statistics are atomic counters, and
+/// only the threads reaching a node while it is being expanded wait for
+/// its children. Each iteration expands a leaf and plays out a single one
+/// of its children.
+///
+/// Random events are resolved as in `MCTS`: the outcome of a chance node
+/// is drawn with its probability, and the rewards of the node are the
+/// expectation of the ones of its outcomes.
+pub struct TreeParallel<T: GameTest> {
+    root: SharedNode<T::Action>,
+    threads: usize,
+    virtual_loss: u32,
+    selection: Box<dyn SelectionPolicy + Send + Sync>,
+    rollout: Box<dyn RolloutPolicy<T> + Send + Sync>,
+    final_move: FinalMove,
+    nodes: AtomicUsize,
+    /// Final rewards if the action of the root ended the game.
+    terminal: Option<Rewards>,
+    /// Seeds the generator of each thread.
+    rng: StdRng,
+}
+
+impl<T> TreeParallel<T>
+where
+    T: GameTest + Clone + Send,
+    T::Action: Send + Sync,
+{
+    pub fn new(threads: usize) -> TreeParallel<T> {
+        TreeParallel {
+            root: SharedNode::new(None, 0, 0),
+            threads,
+            virtual_loss: 1,
+            selection: Box::new(Ucb1::default()),
+            rollout: Box::new(UniformRandom),
+            final_move: FinalMove::default(),
+            nodes: AtomicUsize::new(1),
+            terminal: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Seeds the random generators of the threads. The tree still depends
+    /// on how the threads interleave.
+    pub fn with_seed(mut self, seed: u64) -> TreeParallel<T> {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Number of lost plays added to a node for each thread below it, `1`
+    /// by default.
+    pub fn with_virtual_loss(mut self, virtual_loss: u32) -> TreeParallel<T> {
+        self.virtual_loss = virtual_loss;
+        self
+    }
+
+    pub fn with_selection<S>(mut self, selection: S) -> TreeParallel<T>
+    where
+        S: SelectionPolicy + Send + Sync + 'static,
+    {
+        self.selection = Box::new(selection);
+        self
+    }
+
+    pub fn with_rollout<R>(mut self, rollout: R) -> TreeParallel<T>
+    where
+        R: RolloutPolicy<T> + Send + Sync + 'static,
+    {
+        self.rollout = Box::new(rollout);
+        self
+    }
+
+    /// `MaxRobust` never searches further and falls back to `Robust`.
+    pub fn with_final_move(mut self, final_move: FinalMove) -> TreeParallel<T> {
+        self.final_move = final_move;
+        self
+    }
+
+    /// Number of nodes in the tree.
+    pub fn nodes(&self) -> usize {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    /// Number of playouts backed up through the root.
+    pub fn plays(&self) -> u32 {
+        self.root.plays.load(Ordering::Relaxed)
+    }
+
+    /// Approximate memory used by the tree, in bytes.
+    pub fn memory(&self) -> usize {
+        let players = self.root.rewards.len();
+        self.nodes()
+            * (std::mem::size_of::<SharedNode<T::Action>>()
+                + 2 * players * std::mem::size_of::<AtomicU64>())
+    }
+
+    /// Picks the child of `node` to explore, or draws the outcome of a
+    /// random event, and adds a virtual loss to it.
+    fn explore<'a>(
+        &self,
+        node: &SharedNode<T::Action>,
+        children: &'a [SharedNode<T::Action>],
+        rng: &mut dyn rand::RngCore,
+    ) -> &'a SharedNode<T::Action> {
+        let child = if is_chance(children) {
+            &children[sample(children.iter().map(|c| c.probability.unwrap()), rng)]
+        } else {
+            let parent_plays =
+                node.plays.load(Ordering::Relaxed) + node.virtual_loss.load(Ordering::Relaxed);
+            let prior = 1.0 / children.len() as f64;
+
+            let mut score = f64::NEG_INFINITY;
+            children.iter().fold(&children[0], |acc, c| {
+                let vl = c.virtual_loss.load(Ordering::Relaxed);
+                let stats = c.snapshot(vl).child_stats(prior);
+                let new_score = self.selection.score(parent_plays, &stats, rng);
+                if new_score > score {
+                    score = new_score;
+                    c
+                } else {
+                    acc
+                }
+            })
+        };
+        child
+            .virtual_loss
+            .fetch_add(self.virtual_loss, Ordering::Relaxed);
+        child
+    }
+
+    /// Walks down from the root to a leaf, playing the actions on `g`, and
+    /// returns the path with the final rewards if the last action ended the
+    /// game. Every node but the root gets a virtual loss.
+    fn select<'a>(
+        &'a self,
+        g: &mut T,
+        rng: &mut dyn rand::RngCore,
+    ) -> (Vec<&'a SharedNode<T::Action>>, Option<Rewards>) {
+        let mut path = vec![&self.root];
+        if self.terminal.is_some() {
+            return (path, self.terminal.clone());
+        }
+        let mut node = &self.root;
+        while let Some(children) = node.children.get() {
+            if children.is_empty() {
+                break;
+            }
+            node = self.explore(node, children, rng);
+            path.push(node);
+            let terminal = Tree::play_out(g, node.action.clone().unwrap());
+            if terminal.is_some() {
+                return (path, terminal);
+            }
+        }
+        (path, None)
+    }
+
+    /// Children of `node`, created from the actions of `g`, or the outcomes
+    /// of its random event, by the first thread to get there while the
+    /// others wait.
+    fn expand<'a>(&self, node: &'a SharedNode<T::Action>, g: &T) -> &'a [SharedNode<T::Action>] {
+        node.children.get_or_init(|| {
+            let player = g.current_player();
+            let players = g.players();
+            let mut actions: Vec<(T::Action, Option<f64>)> = g
+                .chance_outcomes()
+                .into_iter()
+                .map(|(a, p)| (a, Some(p)))
+                .collect();
+            if actions.is_empty() {
+                actions = g.valid_actions().into_iter().map(|a| (a, None)).collect();
+            }
+            let children: Vec<_> = actions
+                .into_iter()
+                .map(|(a, probability)| {
+                    let mut child = SharedNode::new(Some(a), player, players);
+                    child.probability = probability;
+                    child
+                })
+                .collect();
+            self.nodes.fetch_add(children.len(), Ordering::Relaxed);
+            children
+        })
+    }
+
+    /// Adds `rewards` to every node of `path` and removes the virtual losses
+    /// that `select` added.
+    fn backprop(&self, path: &[&SharedNode<T::Action>], rewards: &[f64]) {
+        for (i, n) in path.iter().enumerate() {
+            n.push(rewards);
+            if i > 0 {
+                n.virtual_loss
+                    .fetch_sub(self.virtual_loss, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// One iteration of the search from the root.
+    fn train(&self, g: &T, rng: &mut dyn rand::RngCore) {
+        let mut g = g.clone();
+        let (mut path, terminal) = self.select(&mut g, rng);
+
+        let rewards = match terminal {
+            Some(rewards) => rewards,
+            None => {
+                let leaf = *path.last().unwrap();
+                let children = self.expand(leaf, &g);
+                if children.is_empty() {
+                    share(g.players(), None, 1.0)
+                } else {
+                    let child = self.explore(leaf, children, rng);
+                    path.push(child);
+                    match Tree::play_out(&mut g, child.action.clone().unwrap()) {
+                        Some(rewards) => rewards,
+                        None => Tree::simulate(&mut g, &*self.rollout, rng, None),
+                    }
+                }
+            }
+        };
+
+        self.backprop(&path, &rewards);
+    }
+
+    /// Searches `g` with every thread until one of the limits of `budget`
+    /// is reached, and returns which one. Iterations are counted over all
+    /// threads.
+    pub fn search(&mut self, g: &T, budget: &Budget) -> StopReason {
+        let players = g.players();
+        if self.root.rewards.len() != players {
+            let children = self.root.children.take();
+            self.root = SharedNode::new(self.root.action.take(), self.root.player, players);
+            if let Some(children) = children {
+                let _ = self.root.children.set(children);
+            }
+        }
+
+        let rngs: Vec<StdRng> = (0..self.threads)
+            .map(|_| StdRng::from_rng(&mut self.rng).unwrap())
+            .collect();
+        let start = Instant::now();
+        let iterations = AtomicU32::new(0);
+        let this = &*self;
+        let reasons: Vec<StopReason> = thread::scope(|s| {
+            let handles: Vec<_> = rngs
+                .into_iter()
+                .map(|mut rng| {
+                    let g = g.clone();
+                    let iterations = &iterations;
+                    s.spawn(move || {
+                        loop {
+                            // Claims the next iteration, so that the total is
+                            // exact whatever the number of threads.
+                            let done = iterations.fetch_add(1, Ordering::Relaxed);
+                            let reached = budget.reached(done, start, this.nodes(), this.memory());
+                            if let Some(reason) = reached {
+                                return reason;
+                            }
+                            this.train(&g, &mut rng);
+                        }
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        reasons[0]
+    }
+
+    fn children(&self) -> Vec<Node<T::Action>> {
+        self.root
+            .children
+            .get()
+            .map(|c| c.iter().map(|n| n.snapshot(0)).collect())
+            .unwrap_or_default()
+    }
+
+    fn reroot(&mut self, g: &mut T, index: usize) {
+        let mut children = self.root.children.take().unwrap();
+        self.root = children.swap_remove(index);
+        self.nodes.store(self.root.size(), Ordering::Relaxed);
+        self.terminal = Tree::play_out(g, self.root.action.clone().unwrap());
+    }
+
+    /// Plays the best move found so far on `g` and returns it, `None` when
+    /// no move was searched, such as before a search or once the game is
+    /// over, or while a random event is pending.
+    pub fn play_best_move(&mut self, g: &mut T) -> Option<T::Action> {
+        if !g.chance_outcomes().is_empty() {
+            return None;
+        }
+        let snapshot = self.children();
+        let children: Vec<&Node<T::Action>> = snapshot.iter().collect();
+        let index = match self.final_move.index(&children) {
+            Some(index) => index,
+            None => FinalMove::robust_index(&children)?,
+        };
+        self.reroot(g, index);
+        self.root.action.clone()
+    }
+
+    /// Plays `play` on `g` and moves the root of the tree to it, see
+    /// `MCTS::apply_ext`.
+    pub fn apply_ext(
+        &mut self,
+        g: &mut T,
+        play: T::Action,
+    ) -> Result<(), IllegalAction<T::Action>> {
+        if !is_legal(g, &play) {
+            return Err(IllegalAction(play));
+        }
+        let index = self
+            .root
+            .children
+            .get()
+            .and_then(|c| c.iter().position(|n| n.action.as_ref() == Some(&play)));
+        match index {
+            Some(index) => self.reroot(g, index),
+            None => {
+                self.root = SharedNode::new(Some(play.clone()), g.current_player(), g.players());
+                self.nodes.store(1, Ordering::Relaxed);
+                self.terminal = Tree::play_out(g, play);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: GameTest> Debug for TreeParallel<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "TreeParallel {{ threads: {}, plays: {}, nodes: {} }}",
+            self.threads,
+            self.root.plays.load(Ordering::Relaxed),
+            self.nodes.load(Ordering::Relaxed)
+        )
+    }
+}
@@ -0,0 +1,20 @@
+/// Progressive widening, see `MCTS::with_widening`: a node visited `plays`
+/// times has `ceil(c * plays ^ alpha)` children unlocked, at least one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Widening {
+    pub c: f64,
+    pub alpha: f64,
+}
+
+impl Default for Widening {
+    fn default() -> Widening {
+        Widening { c: 1.0, alpha: 0.5 }
+    }
+}
+
+impl Widening {
+    /// Number of children unlocked after `plays` visits.
+    pub fn unlocked(self, plays: u32) -> usize {
+        ((self.c * (plays as f64).powf(self.alpha)).ceil() as usize).max(1)
+    }
+}
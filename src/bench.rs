@@ -0,0 +1,75 @@
+use crate::{Piece, TicTacToe};
+use mcts::{Budget, GameTest, PlayRes, TreeParallel, WinningMove};
+use std::time::{Duration, Instant};
+use tictactoe::{Check, Game};
+
+const BENCH_TIME: Duration = Duration::from_secs(1);
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+
+/// Plain tic-tac-toe, X starts.
+#[derive(Clone)]
+struct Morpion {
+    game: Game,
+    next: Check,
+}
+
+impl GameTest for Morpion {
+    type Action = usize;
+
+    fn play(&mut self, play: usize) -> PlayRes {
+        let piece = self.next;
+        self.next = match piece {
+            Check::X => Check::O,
+            _ => Check::X,
+        };
+        match self.game.play(play, piece) {
+            Check::None if self.game.valid_moves().is_empty() => PlayRes::Draw,
+            Check::None => PlayRes::Nothing,
+            _ => PlayRes::Win,
+        }
+    }
+
+    fn valid_actions(&self) -> Vec<usize> {
+        self.game.valid_moves()
+    }
+
+    fn current_player(&self) -> usize {
+        match self.next {
+            Check::O => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Playouts per second of a tree-parallel search from the start of `g`,
+/// for each number of threads.
+fn scaling<T>(name: &str, g: &T)
+where
+    T: GameTest + Clone + Send,
+    T::Action: Send + Sync,
+{
+    println!("{}", name);
+    println!("threads   playouts/s   speedup");
+    let mut base = None;
+    for &threads in THREADS.iter() {
+        let mut ai = TreeParallel::new(threads).with_rollout(WinningMove);
+        let start = Instant::now();
+        ai.search(g, &Budget::new().time(BENCH_TIME));
+        let rate = ai.plays() as f64 / start.elapsed().as_secs_f64();
+        let base = *base.get_or_insert(rate);
+        println!("{:7}   {:10.0}   {:7.2}", threads, rate, rate / base);
+    }
+}
+
+/// Measures how tree-parallel search scales with the number of threads on
+/// tic-tac-toe and ultimate tic-tac-toe.
+pub fn run() -> crate::Result<()> {
+    let morpion = Morpion {
+        game: Game::new(),
+        next: Check::X,
+    };
+    scaling("tic-tac-toe", &morpion);
+    println!();
+    scaling("ultimate tic-tac-toe", &TicTacToe::new(Piece::X)?);
+    Ok(())
+}
@@ -1,6 +1,16 @@
+use mcts::{Budget, GameTest, PlayRes, Priors, Rave, Widening, WinningMove, MCTS};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
+use std::time::Duration;
+
+mod bench;
+
+const TURN_TIME: Duration = Duration::from_millis(90);
+
 const OFFSET: [usize; 9] = [0, 3, 6, 27, 30, 33, 54, 57, 60];
 
-type Result<T> = std::result::Result<T, Box<std::error::Error>>;
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 macro_rules! check_line {
     ($b:expr, $i:expr, $i1:expr, $i2:expr, $i3:expr) => {
@@ -11,7 +21,7 @@ macro_rules! check_line {
 macro_rules! returner {
     ( $b:expr, $fb:expr, $i:expr => $([$i1:expr, $i2:expr, $i3:expr]),* ) => {
         $(
-            if check_line!($b, $i, $i1, $i2, $i3) {
+            if $b[$i + $i1] != $fb && check_line!($b, $i, $i1, $i2, $i3) {
                 return $b[$i + $i1]
             }
         )*
@@ -19,7 +29,7 @@ macro_rules! returner {
     };
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Piece {
     Empty,
     X,
@@ -48,7 +58,7 @@ impl Index for usize {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Position(usize, usize);
 
 impl Position {
@@ -58,10 +68,11 @@ impl Position {
         x + 9 * y
     }
 
+    /// First position of the square the next player is sent to.
     #[inline]
-    fn get_first(self) -> Self {
+    fn get_next_square(self) -> Self {
         let Position(x, y) = self;
-        Position((x / 3) * 3, (y / 3) * 3)
+        Position((x % 3) * 3, (y % 3) * 3)
     }
 
     fn from_string(s: String) -> Result<Position> {
@@ -90,7 +101,7 @@ mod position_error {
             "String is empty"
         }
 
-        fn cause(&self) -> Option<&std::error::Error> {
+        fn cause(&self) -> Option<&dyn std::error::Error> {
             None
         }
     }
@@ -103,7 +114,7 @@ mod tic_tac_toe_error {
     pub struct PieceNotAllowd(pub crate::Piece);
     impl std::fmt::Display for PieceNotAllowd {
         fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            write!(f, "{:?}", self)
+            write!(f, "PieceNotAllowd({:?})", self.0)
         }
     }
     impl std::error::Error for PieceNotAllowd {
@@ -111,7 +122,7 @@ mod tic_tac_toe_error {
             "You cant use this piece here"
         }
 
-        fn cause(&self) -> Option<&std::error::Error> {
+        fn cause(&self) -> Option<&dyn std::error::Error> {
             None
         }
     }
@@ -120,7 +131,7 @@ mod tic_tac_toe_error {
     pub struct NotAValidMove(pub Position, pub Vec<Position>);
     impl std::fmt::Display for NotAValidMove {
         fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            write!(f, "{:?}", self)
+            write!(f, "NotAValidMove({:?}, {:?})", self.0, self.1)
         }
     }
     impl std::error::Error for NotAValidMove {
@@ -128,7 +139,7 @@ mod tic_tac_toe_error {
             "Not a valid move"
         }
 
-        fn cause(&self) -> Option<&std::error::Error> {
+        fn cause(&self) -> Option<&dyn std::error::Error> {
             None
         }
     }
@@ -145,7 +156,7 @@ mod tic_tac_toe_error {
             "The index is out of game bound"
         }
 
-        fn cause(&self) -> Option<&std::error::Error> {
+        fn cause(&self) -> Option<&dyn std::error::Error> {
             None
         }
     }
@@ -192,15 +203,16 @@ impl TicTacToe {
                     } = self;
 
                     b[pos] = n;
-                    Ok(TicTacToe {
+                    let mut res = TicTacToe {
                         board: b,
                         next_piece: n.next(),
-                        last_move: if self.check_square_full(pos.position().get_first()) {
-                            None
-                        } else {
-                            Some(pos.position())
-                        },
-                    })
+                        last_move: None,
+                    };
+                    let next = pos.position().get_next_square();
+                    if !res.check_square_full(next.clone()) {
+                        res.last_move = Some(next);
+                    }
+                    Ok(res)
                 }
             }
             _ => Err(tic_tac_toe_error::OutOfBound.into()),
@@ -208,10 +220,7 @@ impl TicTacToe {
     }
 
     fn check_square_full(&self, p: Position) -> bool {
-        match self.check_winner_local(p) {
-            Piece::Empty => false,
-            _ => true,
-        }
+        !matches!(self.check_winner_local(p), Piece::Empty)
     }
 
     fn check_winner_local(&self, p: Position) -> Piece {
@@ -230,7 +239,7 @@ impl TicTacToe {
 
     fn valid_moves(&mut self) -> Vec<usize> {
         let res = self.moves();
-        if res.len() == 0 {
+        if res.is_empty() {
             self.last_move = None;
             self.moves()
         } else {
@@ -271,24 +280,129 @@ impl TicTacToe {
     }
 }
 
+impl GameTest for TicTacToe {
+    type Action = Position;
+
+    fn play(&mut self, play: Position) -> PlayRes {
+        let piece = self.next_piece;
+        match self.clone().place_next_piece(play) {
+            Ok(t) => {
+                *self = t;
+                match self.check_winner() {
+                    Piece::Empty if self.valid_actions().is_empty() => PlayRes::Draw,
+                    Piece::Empty => PlayRes::Nothing,
+                    p if p == piece => PlayRes::Win,
+                    _ => PlayRes::Loose,
+                }
+            }
+            Err(_) => PlayRes::Loose,
+        }
+    }
+
+    fn valid_actions(&self) -> Vec<Position> {
+        self.clone()
+            .valid_moves()
+            .into_iter()
+            .map(Index::position)
+            .collect()
+    }
+
+    fn current_player(&self) -> usize {
+        match self.next_piece {
+            Piece::O => 1,
+            _ => 0,
+        }
+    }
+
+    /// Winning a small board is worth `1.0`, preventing the opponent from
+    /// winning it `0.8`.
+    fn action_prior(&self, action: &Position) -> Option<f64> {
+        let index = action.clone().index();
+        let Position(x, y) = *action;
+        let corner = Position(x - x % 3, y - y % 3);
+        if self.check_winner_local(corner.clone()) != Piece::Empty {
+            return Some(0.5);
+        }
+        let wins = |piece| {
+            let mut t = self.clone();
+            t.board[index] = piece;
+            t.check_winner_local(corner.clone()) == piece
+        };
+        Some(if wins(self.next_piece) {
+            1.0
+        } else if wins(self.next_piece.next()) {
+            0.8
+        } else {
+            0.5
+        })
+    }
+
+    /// Centers of the small boards first, then their corners.
+    fn order_actions(&self, actions: &mut [Position]) {
+        actions.sort_by_key(|Position(x, y)| match (x % 3, y % 3) {
+            (1, 1) => 0,
+            (1, _) | (_, 1) => 2,
+            _ => 1,
+        });
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.board.hash(&mut hasher);
+        self.next_piece.hash(&mut hasher);
+        self.last_move.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
 #[inline]
 fn tri_eq<T: PartialEq>(a: T, b: T, c: T) -> bool {
     a == b && a == c
 }
 
+/// Reads the opponent moves as `x y` on stdin, `-1 -1` when we start, and
+/// answers with our own move. With `--bench`, measures the scaling of the
+/// tree-parallel search instead.
 fn main() -> Result<()> {
-    let mut t = TicTacToe::new(Piece::O)?;
-    t.valid_moves();
-    t = t.place_next_piece(Position(0, 0))?;
-    t.check_winner();
+    if std::env::args().any(|a| a == "--bench") {
+        return bench::run();
+    }
+
+    let mut t = TicTacToe::new(Piece::X)?;
+    let mut ai = MCTS::new()
+        .with_rollout(WinningMove)
+        .with_rave(Rave::Equivalence(1000.0))
+        .with_widening(Widening::default())
+        .with_priors(Priors::ProgressiveBias(1.0))
+        .with_transpositions();
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if !line.starts_with('-') {
+            ai.apply_ext(&mut t, Position::from_string(line)?)?;
+        }
+
+        ai.search(&mut t, &Budget::new().time(TURN_TIME));
+
+        match ai.play_best_move(&mut t) {
+            Some(play) => println!("{}", play.format()),
+            None => break,
+        }
+    }
     Ok(())
 }
 
+#[cfg(test)]
+fn sorted(mut v: Vec<usize>) -> Vec<usize> {
+    v.sort();
+    v
+}
+
 #[test]
 fn create_test() {
-    assert_eq!(TicTacToe::new(Piece::Empty).is_err(), true);
-    assert_eq!(TicTacToe::new(Piece::X).is_ok(), true);
-    assert_eq!(TicTacToe::new(Piece::O).is_ok(), true);
+    assert!(TicTacToe::new(Piece::Empty).is_err());
+    assert!(TicTacToe::new(Piece::X).is_ok());
+    assert!(TicTacToe::new(Piece::O).is_ok());
 }
 
 #[test]
@@ -309,37 +423,28 @@ fn position_convertion() {
 
 #[test]
 fn play_out_of_bound() {
-    assert_eq!(
-        TicTacToe::new(Piece::X)
-            .unwrap()
-            .place_next_piece(81usize.position())
-            .is_err(),
-        true
-    );
+    assert!(TicTacToe::new(Piece::X)
+        .unwrap()
+        .place_next_piece(81usize.position())
+        .is_err());
 }
 
 #[test]
 fn play_same_place() {
-    assert_eq!(
-        TicTacToe::new(Piece::X)
-            .unwrap()
-            .place_next_piece(Position(0, 0))
-            .unwrap()
-            .place_next_piece(Position(1, 1))
-            .unwrap()
-            .place_next_piece(Position(0, 0))
-            .is_err(),
-        true
-    );
-    assert_eq!(
-        TicTacToe::new(Piece::X)
-            .unwrap()
-            .place_next_piece(Position(0, 0))
-            .unwrap()
-            .place_next_piece(Position(0, 0))
-            .is_err(),
-        true
-    );
+    assert!(TicTacToe::new(Piece::X)
+        .unwrap()
+        .place_next_piece(Position(0, 0))
+        .unwrap()
+        .place_next_piece(Position(1, 1))
+        .unwrap()
+        .place_next_piece(Position(0, 0))
+        .is_err());
+    assert!(TicTacToe::new(Piece::X)
+        .unwrap()
+        .place_next_piece(Position(0, 0))
+        .unwrap()
+        .place_next_piece(Position(0, 0))
+        .is_err());
 }
 
 #[test]
@@ -348,8 +453,8 @@ fn right_moves() {
     assert_eq!(t.valid_moves().len(), 81);
     t = t.place_next_piece(Position(0, 0)).unwrap();
     assert_eq!(
-        t.valid_moves().sort(),
-        vec![
+        sorted(t.valid_moves()),
+        sorted(vec![
             Position(0, 1).index(),
             Position(0, 2).index(),
             Position(1, 0).index(),
@@ -358,15 +463,14 @@ fn right_moves() {
             Position(2, 0).index(),
             Position(2, 1).index(),
             Position(2, 2).index(),
-        ]
-        .sort()
+        ])
     );
-    assert_eq!(t.clone().place_next_piece(Position(0, 3)).is_err(), true);
+    assert!(t.clone().place_next_piece(Position(0, 3)).is_err());
 
     t = t.place_next_piece(Position(1, 0)).unwrap();
     assert_eq!(
-        t.valid_moves().sort(),
-        vec![
+        sorted(t.valid_moves()),
+        sorted(vec![
             Position(3, 0).index(),
             Position(3, 1).index(),
             Position(3, 2).index(),
@@ -376,14 +480,13 @@ fn right_moves() {
             Position(5, 0).index(),
             Position(5, 1).index(),
             Position(5, 2).index(),
-        ]
-        .sort()
+        ])
     );
 
     t = t.place_next_piece(Position(3, 0)).unwrap();
     assert_eq!(
-        t.valid_moves().sort(),
-        vec![
+        sorted(t.valid_moves()),
+        sorted(vec![
             Position(0, 1).index(),
             Position(0, 2).index(),
             Position(1, 1).index(),
@@ -391,42 +494,37 @@ fn right_moves() {
             Position(2, 0).index(),
             Position(2, 1).index(),
             Position(2, 2).index(),
-        ]
-        .sort()
+        ])
     );
 
     t = t.place_next_piece(Position(1, 1)).unwrap();
-    // assert_eq!(
-    //     t.valid_moves().sort(),
-    //     vec![
-    //         Position(3, 3).index(),
-    //         Position(3, 4).index(),
-    //         Position(3, 5).index(),
-    //         Position(4, 3).index(),
-    //         Position(4, 4).index(),
-    //         Position(4, 5).index(),
-    //         Position(5, 3).index(),
-    //         Position(5, 4).index(),
-    //         Position(5, 5).index(),
-    //     ]
-    //     .sort()
-    // );
+    assert_eq!(
+        sorted(t.valid_moves()),
+        sorted(vec![
+            Position(3, 3).index(),
+            Position(3, 4).index(),
+            Position(3, 5).index(),
+            Position(4, 3).index(),
+            Position(4, 4).index(),
+            Position(4, 5).index(),
+            Position(5, 3).index(),
+            Position(5, 4).index(),
+            Position(5, 5).index(),
+        ])
+    );
 }
 
 #[test]
 fn play_valid_but_not_right_move() {
-    assert_eq!(
-        TicTacToe::new(Piece::X)
-            .unwrap()
-            .place_next_piece(Position(0, 0))
-            .unwrap()
-            .place_next_piece(Position(0, 3))
-            .is_err(),
-        true
-    );
+    assert!(TicTacToe::new(Piece::X)
+        .unwrap()
+        .place_next_piece(Position(0, 0))
+        .unwrap()
+        .place_next_piece(Position(0, 3))
+        .is_err());
 }
 
-// #[test]
+#[test]
 fn basic_win() {
     let mut t = TicTacToe::new(Piece::X)
         .unwrap()
@@ -444,5 +542,5 @@ fn basic_win() {
 
     t = t.place_next_piece(Position(1, 2)).unwrap();
 
-    assert_eq!(t.check_winner_local(Position(0, 0)), Piece::X);
+    assert_eq!(t.check_winner_local(Position(0, 0)), Piece::O);
 }
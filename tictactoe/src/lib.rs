@@ -1,3 +1,14 @@
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum Check {
     None,
@@ -10,6 +21,12 @@ pub struct Game {
     b: Vec<Check>,
 }
 
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
 impl Game {
     pub fn new() -> Game {
         let mut res = Game {
@@ -24,9 +41,7 @@ impl Game {
     pub fn show(&self) -> String {
         let mut res = String::new();
 
-        let mut index = 0;
-
-        for i in &self.b {
+        for (index, i) in self.b.iter().enumerate() {
             if index % 3 == 0 {
                 res.push('\n');
             }
@@ -36,8 +51,6 @@ impl Game {
                 Check::O => '0',
                 Check::X => 'X',
             });
-
-            index += 1;
         }
 
         res
@@ -53,25 +66,12 @@ impl Game {
     }
 
     pub fn check_win(&self) -> Check {
-        if self.b[0] == self.b[1] && self.b[0] == self.b[2] {
-            self.b[0]
-        } else if self.b[3] == self.b[4] && self.b[3] == self.b[5] {
-            self.b[3]
-        } else if self.b[6] == self.b[7] && self.b[6] == self.b[8] {
-            self.b[6]
-        } else if self.b[0] == self.b[3] && self.b[0] == self.b[6] {
-            self.b[0]
-        } else if self.b[1] == self.b[4] && self.b[1] == self.b[7] {
-            self.b[1]
-        } else if self.b[2] == self.b[5] && self.b[2] == self.b[8] {
-            self.b[2]
-        } else if self.b[0] == self.b[4] && self.b[0] == self.b[8] {
-            self.b[0]
-        } else if self.b[2] == self.b[4] && self.b[2] == self.b[6] {
-            self.b[2]
-        } else {
-            Check::None
-        }
+        LINES
+            .iter()
+            .find(|&&[a, b, c]| {
+                self.b[a] != Check::None && self.b[a] == self.b[b] && self.b[a] == self.b[c]
+            })
+            .map_or(Check::None, |l| self.b[l[0]])
     }
 
     pub fn valid_moves(&self) -> Vec<usize> {